@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use bevy::audio::{AddAudioSource, Decodable, Source, Volume};
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::settings::Settings;
+use crate::GameState;
+
+const SAMPLE_RATE: u32 = 44_100;
+// fade in and out of every note so it starts and stops without a click
+const NOTE_FADE: f32 = 0.005;
+
+// a sound played for something that happened in the game or the menu
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Cue {
+    Pickup,
+    Drop,
+    IllegalDrop,
+    Pass,
+    TurnChange,
+    OutOfMoves,
+    GameEnd,
+    Click,
+}
+
+// volume setting a cue is played with, the master volume applies to all of them
+enum Channel {
+    Effects,
+    Ui,
+}
+
+impl Cue {
+    fn channel(self) -> Channel {
+        match self {
+            Cue::Pickup | Cue::Drop | Cue::IllegalDrop | Cue::Pass => Channel::Effects,
+            Cue::TurnChange | Cue::OutOfMoves | Cue::GameEnd | Cue::Click => Channel::Ui,
+        }
+    }
+
+    // frequencies and lengths in seconds of the notes, one after another
+    fn notes(self) -> &'static [(f32, f32)] {
+        match self {
+            Cue::Pickup => &[(660.0, 0.05)],
+            // the drop is one of the recorded sounds
+            Cue::Drop => &[],
+            Cue::IllegalDrop => &[(220.0, 0.08), (185.0, 0.14)],
+            Cue::Pass => &[(440.0, 0.08), (330.0, 0.12)],
+            Cue::TurnChange => &[(523.0, 0.07), (659.0, 0.1)],
+            Cue::OutOfMoves => &[(392.0, 0.1), (330.0, 0.1), (262.0, 0.22)],
+            Cue::GameEnd => &[(523.0, 0.12), (659.0, 0.12), (784.0, 0.12), (1047.0, 0.35)],
+            Cue::Click => &[(880.0, 0.03)],
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct SoundEvent(pub Cue);
+
+// synthesized notes of a cue
+#[derive(Asset, TypePath)]
+pub struct Tone {
+    notes: Vec<(f32, f32)>,
+}
+
+pub struct ToneDecoder {
+    notes: Vec<(f32, f32)>,
+    note: usize,
+    sample: u32,
+}
+
+impl Decodable for Tone {
+    type DecoderItem = f32;
+    type Decoder = ToneDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        ToneDecoder { notes: self.notes.clone(), note: 0, sample: 0 }
+    }
+}
+
+impl Iterator for ToneDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let &(frequency, length) = self.notes.get(self.note)?;
+        let time = self.sample as f32 / SAMPLE_RATE as f32;
+        if time >= length {
+            self.note += 1;
+            self.sample = 0;
+            return self.next();
+        }
+        self.sample += 1;
+
+        // quick fade in, then dying away like a struck bell
+        let envelope = (time / NOTE_FADE).min(1.0) * (1.0 - time / length).powi(2);
+        let wave = (TAU * frequency * time).sin() + 0.3 * (2.0 * TAU * frequency * time).sin();
+        Some(0.4 * envelope * wave)
+    }
+}
+
+impl Source for ToneDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(self.notes.iter().map(|&(_, length)| length).sum()))
+    }
+}
+
+#[derive(Resource, Default)]
+struct Sounds {
+    drops: Vec<Handle<AudioSource>>,
+    tones: HashMap<Cue, Handle<Tone>>,
+}
+
+pub fn audio_plugin(app: &mut App) {
+    app
+        .add_audio_source::<Tone>()
+        .add_event::<SoundEvent>()
+        .init_resource::<Sounds>()
+        .add_systems(Startup, load_sounds)
+        .add_systems(OnEnter(GameState::GameEnd), game_end_sound)
+        .add_systems(Update, button_click_sound)
+        .add_systems(PostUpdate, play_sounds);
+}
+
+fn load_sounds(asset_server: Res<AssetServer>, mut tones: ResMut<Assets<Tone>>, mut sounds: ResMut<Sounds>) {
+    sounds.drops = (1..=5).map(|index| asset_server.load(format!("drop{index}.ogg"))).collect();
+    for cue in [Cue::Pickup, Cue::IllegalDrop, Cue::Pass, Cue::TurnChange, Cue::OutOfMoves, Cue::GameEnd, Cue::Click] {
+        sounds.tones.insert(cue, tones.add(Tone { notes: cue.notes().to_vec() }));
+    }
+}
+
+fn game_end_sound(mut ev_sound: EventWriter<SoundEvent>) {
+    ev_sound.send(SoundEvent(Cue::GameEnd));
+}
+
+fn button_click_sound(
+    interactions: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    mut ev_sound: EventWriter<SoundEvent>,
+) {
+    if interactions.iter().any(|interaction| *interaction == Interaction::Pressed) {
+        ev_sound.send(SoundEvent(Cue::Click));
+    }
+}
+
+// each cue once per frame, however many events asked for it
+fn play_sounds(
+    mut ev_sound: EventReader<SoundEvent>,
+    settings: Res<Settings>,
+    sounds: Res<Sounds>,
+    mut commands: Commands,
+) {
+    let mut cues: Vec<Cue> = Vec::new();
+    for SoundEvent(cue) in ev_sound.read() {
+        if !cues.contains(cue) {
+            cues.push(*cue);
+        }
+    }
+    if settings.muted {
+        return;
+    }
+
+    for cue in cues {
+        let volume = match cue.channel() {
+            Channel::Effects => settings.effects_volume,
+            Channel::Ui => settings.ui_volume,
+        };
+        let playback = PlaybackSettings::DESPAWN.with_volume(Volume::new(volume));
+        if cue == Cue::Drop {
+            if let Some(source) = sounds.drops.choose(&mut rand::thread_rng()) {
+                commands.spawn(AudioBundle { source: source.clone(), settings: playback });
+            }
+        } else if let Some(source) = sounds.tones.get(&cue) {
+            commands.spawn(AudioSourceBundle { source: source.clone(), settings: playback });
+        }
+    }
+}
@@ -1,24 +1,82 @@
 use std::f32::consts::SQRT_3;
 
+use bevy::input::InputSystem;
 use bevy::prelude::*;
 use std::collections::HashMap;
 
 use crate::hex::Rotation;
 use crate::GameState;
 use crate::despawn_screen;
+use crate::audio::{Cue, SoundEvent};
+use crate::locale::Locale;
 use crate::hex::Hex;
 use crate::game::update::*;
+use crate::game::hint::*;
+use crate::game::keyboard::*;
+use crate::game::gamepad::*;
+use crate::game::touch::*;
+use crate::game::camera::*;
+use crate::game::network::*;
+use crate::game::palette::*;
+use crate::game::controls::*;
+use crate::game::animation::*;
+use crate::game::tray::*;
+pub use crate::game::network::ServerAddress;
+pub use crate::game::discovery::{discover_lan_games, start_discovery, stop_discovery, LanDiscovery, LanGame};
+pub use crate::game::server::{run_server, AbsentSeatPolicy, LobbySettings, SUPPORTED_PLAYER_COUNTS};
+pub use crate::game::league::{Match, MatchFormat};
+pub use crate::game::palette::{ColorPalette, PALETTE_SIZE};
+pub use crate::game::controls::{Action, Binding, Bindings, BINDING_SLOTS};
+pub use crate::game::animation::AnimationSpeed;
 use Rotation::*;
 
 mod setup;
 mod update;
+mod hint;
+mod keyboard;
+mod gamepad;
+mod touch;
+mod camera;
+mod network;
+mod server;
+mod discovery;
+mod league;
+mod palette;
+mod controls;
+mod animation;
+mod tray;
 
 pub fn game_plugin(app: &mut App) {
     app
         .add_event::<PassTurnEvent>()
-        .add_systems(OnEnter(GameState::Game), (despawn_screen::<OnGameScreen>, setup::call).chain())
-        .add_systems(Update, (pickup_piece, board_system, on_pass_turn).run_if(in_state(GameState::Game)))
-        .add_systems(Update, (move_piece, put_piece).chain().run_if(in_state(GameState::Game)))
+        .add_event::<TurnPassedEvent>()
+        .add_event::<PlacementRequestEvent>()
+        .add_event::<HintEvent>()
+        .add_event::<PieceControlEvent>()
+        .add_event::<UndoEvent>()
+        .init_resource::<ActionState>()
+        .add_systems(Startup, create_player_patterns)
+        .add_systems(PreUpdate, update_action_state.after(InputSystem))
+        .add_systems(OnEnter(GameState::Game), (despawn_screen::<OnGameScreen>, apply_display_settings, setup::call, spawn_tray_summaries, reset_camera).chain())
+        .add_systems(Update, (zoom_camera, pan_camera, rotate_view_to_player).run_if(in_state(GameState::Game)))
+        .add_systems(Update, (connect_to_server, reconnect_to_server.run_if(in_state(GameState::Game)), receive_messages).chain())
+        // a pass received from the server is handled in the same frame, before the next move is applied
+        .add_systems(Update, (pass_turn_action, gamepad_buttons, request_pass, send_requests, on_pass_turn, undo_action, undo_move).chain().after(receive_messages).run_if(in_state(GameState::Game)))
+        .add_systems(Update, (
+            skip_animations,
+            (pickup_piece, touch_scroll_tray).chain(),
+            (keyboard_select_piece, gamepad_select_piece).chain(),
+            (keyboard_move_cursor, gamepad_move_cursor, touch_rotate).chain(),
+            move_piece,
+            put_piece,
+            ghost_system
+        ).chain().after(crate::world_cursor_system).run_if(in_state(GameState::Game)))
+        // pieces placed just before the game ends still land
+        .add_systems(Update, (layout_trays, animate_tweens, board_system, board_pattern_system).chain().after(put_piece).run_if(in_state(GameState::Game).or_else(in_state(GameState::GameEnd))))
+        .add_systems(Update, (clear_stale_hint, on_hint, pulse_hinted_blocks).chain().run_if(in_state(GameState::Game)))
+        .add_systems(Update, update_tray_summaries.run_if(in_state(GameState::Game)))
+        // after the commands of the moves are applied
+        .add_systems(PostUpdate, (announce_pickup, announce_turn).run_if(in_state(GameState::Game)))
         .add_systems(OnEnter(GameState::Menu), despawn_screen::<OnGameScreen>)
         .add_systems(OnExit(GameState::GameEnd), despawn_screen::<OnGameScreen>);
 }
@@ -27,28 +85,229 @@ pub fn game_plugin(app: &mut App) {
 pub struct Game {
     original_transform: Transform,
     mouse_offset: Vec2,
+    // hex under the held piece when it is moved with the keyboard or a gamepad
+    hex_cursor: Option<Hex>,
+    // finger that holds the piece on a touch screen
+    touch_id: Option<u64>,
+    // vertical offset of each tray scrolled by touch
+    tray_scroll: [f32; MAX_PLAYERS],
+    snapped_hexes: Vec<Hex>,
     board: Board,
     player_count: usize,
     pub current_player: usize,
+    // name and color of every seat
+    players: Vec<PlayerProfile>,
+    palette: ColorPalette,
+    show_patterns: bool,
+    // player who made the first move, rotates with every rematch
+    first_player: usize,
     pub winner_player: Option<usize>,
-    drop_audio_handles: Vec<Handle<AudioSource>>,
+    pub results: Option<GameResults>,
     pass_turn_count: usize,
+    hint: Option<Hint>,
+    pub show_corners: bool,
+    pub rotate_view: bool,
+    // only the tray of the current player is shown, the others are summed up
+    pub focus_tray: bool,
+    player_starts: Vec<Hex>,
+    // seat of this client in a network game
+    seat: Option<usize>,
+    // the server has not answered the last move of this client yet
+    awaiting_server: bool,
+    // given by the server to take the seat back after a lost connection
+    session_token: Option<u64>,
+    // moves the server sends again after a reconnect that were applied already
+    skip_moves: usize,
+    // moves of the server history applied so far
+    received_moves: usize,
+    // players whose connection to the server is lost
+    absent_players: Vec<usize>,
+    // watches a network game without a seat
+    spectator: bool,
+    move_history: Vec<MoveRecord>,
+    // local moves that can be taken back, the last one first
+    undo_moves: Vec<UndoMove>,
 }
 
 impl Game {
-    pub fn new(player_count: usize) -> Self {
+    pub fn new(player_count: usize, locale: &Locale) -> Self {
+        Self::with_players(PlayerProfile::defaults(player_count, locale))
+    }
+
+    pub fn with_players(players: Vec<PlayerProfile>) -> Self {
+        Self {
+            player_count: players.len(),
+            players,
+            ..default()
+        }
+    }
+
+    // next game of the match with the same options, started by the next player
+    pub fn rematch(&self) -> Self {
+        let first_player = (self.first_player + 1) % self.player_count;
         Self {
-            player_count,
+            player_count: self.player_count,
+            players: self.players.clone(),
+            current_player: first_player,
+            first_player,
             ..default()
+        }.with_options_of(self)
+    }
+
+    // options picked in the previous game of the match
+    fn with_options_of(self, previous: &Game) -> Self {
+        Self {
+            show_corners: previous.show_corners,
+            rotate_view: previous.rotate_view,
+            focus_tray: previous.focus_tray,
+            ..self
         }
     }
+
+    pub fn player_name(&self, player: usize) -> &str {
+        &self.players[player].name
+    }
+
+    // piece sets without a player, like the extra sets of the two player game,
+    // get the palette colors nobody picked
+    fn palette_index(&self, player: usize) -> usize {
+        match self.players.get(player) {
+            Some(profile) => profile.color,
+            None => (0..PALETTE_SIZE)
+                .filter(|color| self.players.iter().all(|profile| profile.color != *color))
+                .nth(player - self.players.len())
+                .unwrap_or(0)
+        }
+    }
+
+    pub fn player_color(&self, player: usize) -> Color {
+        self.palette.color(self.palette_index(player))
+    }
+
+    fn player_color_darken(&self, player: usize) -> Color {
+        shade(self.player_color(player), 0.9, |lightness| lightness * 0.8)
+    }
+
+    fn player_color_light(&self, player: usize) -> Color {
+        shade(self.player_color(player), 1.0, |lightness| lightness + (1.0 - lightness) * 0.6)
+    }
+
+    fn player_color_pale(&self, player: usize) -> Color {
+        shade(self.player_color(player), 1.0, |lightness| lightness + (1.0 - lightness) * 0.84)
+    }
+
+    pub fn is_network_game(&self) -> bool {
+        self.seat.is_some() || self.spectator
+    }
+
+    pub fn seat(&self) -> Option<usize> {
+        self.seat
+    }
+
+    pub fn is_spectator(&self) -> bool {
+        self.spectator
+    }
+
+    pub fn is_absent(&self, player: usize) -> bool {
+        self.absent_players.contains(&player)
+    }
+
+    pub fn move_history(&self) -> &[MoveRecord] {
+        &self.move_history
+    }
+
+    // in a network game only the seated player moves, one move at a time,
+    // spectators never do
+    fn is_local_turn(&self) -> bool {
+        match self.seat {
+            Some(seat) => seat == self.current_player && !self.awaiting_server,
+            None => !self.spectator
+        }
+    }
+
+    pub fn no_moves_hinted(&self) -> bool {
+        matches!(&self.hint, Some(hint) if hint.hexes.is_empty())
+    }
+}
+
+struct Hint {
+    player: usize,
+    // index of the suggestion, increments on every press
+    index: usize,
+    // empty when there is no legal placement
+    hexes: Vec<Hex>,
+}
+
+// shown on the results screen when the game ends
+pub struct GameResults {
+    // ordered by rank
+    pub players: Vec<PlayerResult>,
+    pub decided_by: TiebreakRule,
+}
+
+pub struct PlayerResult {
+    pub player: usize,
+    pub remaining_blocks: usize,
+    pub largest_piece: usize,
+    pub pieces_placed: usize,
+    // players with equal results share the rank
+    pub rank: usize,
+}
+
+// rule of the game that picked the winner
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TiebreakRule {
+    FewestBlocks,
+    SmallestLargestPiece,
+    PieceValues,
+    // the players left with the same pieces
+    Draw,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveRecord {
+    Placed { player: usize, blocks: NumberOfBlocks },
+    Passed { player: usize },
 }
 
 type NumberOfBlocks = usize;
 
+// what a move changed, to restore it on undo
+enum UndoMove {
+    Placed { player: usize, shape: usize, cells: Vec<(Hex, Cell)>, tray_transform: Transform, pass_turn_count: usize },
+    Passed { player: usize, pass_turn_count: usize },
+}
+
+// player asks to pass, in a network game the server decides
 #[derive(Event)]
 pub struct PassTurnEvent;
 
+#[derive(Event)]
+struct TurnPassedEvent;
+
+#[derive(Event)]
+struct PlacementRequestEvent {
+    shape: usize,
+    hexes: Vec<Hex>,
+}
+
+#[derive(Event)]
+pub struct HintEvent;
+
+// takes back the last move of a local game
+#[derive(Event)]
+struct UndoEvent;
+
+// actions on the held piece coming from other inputs than the mouse
+#[derive(Event, Clone, Copy)]
+pub enum PieceControlEvent {
+    RotateCw,
+    RotateCcw,
+    Flip,
+    Place,
+    Cancel,
+}
+
 #[derive(Component)]
 struct BoardComponent;
 
@@ -61,16 +320,30 @@ struct BlockSelectable;
 #[derive(Component)]
 struct Selected;
 
+#[derive(Component)]
+struct Hinted;
+
+#[derive(Component)]
+struct GhostBlock;
+
 #[derive(Component)]
 struct PlayerIndex(usize);
 
 #[derive(Component)]
 struct Piece(NumberOfBlocks);
 
+// index in ALL_PIECES
+#[derive(Component)]
+struct PieceShape(usize);
+
 #[derive(Component)]
 struct OnGameScreen;
 
-#[derive(PartialEq, Default, Debug)]
+// piece dropped on the board of a network game, waiting there for the server to accept the move
+#[derive(Component)]
+struct PendingPlacement;
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
 enum Cell {
     #[default]
     Empty,
@@ -86,8 +359,13 @@ enum PutPieceAction {
 }
 
 const ALL_ROTATIONS: [Rotation; 6] = [Rot0, Rot60Cw, Rot120Cw, Rot180, Rot60Ccw, Rot120Ccw];
+// pieces in the trays are stacked above it, each on its own layer
 const DEFAULT_Z: f32 = 1.0;
-const SELECTED_Z: f32 = 1.0001;
+// above all the pieces in the trays
+const SELECTED_Z: f32 = 20.0;
+const GHOST_Z: f32 = 0.5;
+const GHOST_LEGAL_COLOR: Color = Color::rgba(0.2, 0.9, 0.2, 0.6);
+const GHOST_ILLEGAL_COLOR: Color = Color::rgba(0.9, 0.2, 0.2, 0.6);
 
 const NEIGHBOURS: [Hex; 6] = [Hex { q:  0, r: 1 }, Hex { q: 1, r:   0 }, Hex { q:  0, r: -1 },
                               Hex { q: -1, r: 0 }, Hex { q: 1, r:  -1 }, Hex { q: -1, r:  1 }];
@@ -108,8 +386,25 @@ const HEX_RADIUS: f32 = HEX_WIDTH / 2.0;
 
 type Board = HashMap<Hex, Cell>;
 
-pub fn player_color(player_index: usize) -> Color {
-    Color::hsl(player_index as f32 / MAX_PLAYERS as f32 * 360.0, 1.0, 0.5)
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlayerProfile {
+    pub name: String,
+    // index in the colors of the palette
+    pub color: usize,
+}
+
+impl PlayerProfile {
+    pub fn defaults(count: usize, locale: &Locale) -> Vec<Self> {
+        (0..count).map(|index| PlayerProfile { name: locale.default_player_name(index), color: index }).collect()
+    }
+
+    pub fn color_name(&self, palette: ColorPalette) -> &'static str {
+        palette.color_name(self.color)
+    }
+
+    pub fn display_color(&self, palette: ColorPalette) -> Color {
+        palette.color(self.color)
+    }
 }
 
 fn hex_to_pixel(hex: &Hex) -> Vec2 {
@@ -125,7 +420,5 @@ fn pixel_to_hex(pixel: Vec2) -> Hex {
     Hex::from_fraction(q, r)
 }
 
-fn player_color_darken(player_index: usize) -> Color {
-    Color::hsl(player_index as f32 / MAX_PLAYERS as f32 * 360.0, 0.9, 0.4)
-}
+
 
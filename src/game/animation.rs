@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use crate::hex::Hex;
+use crate::game::*;
+use crate::game::setup::piece_shape;
+use crate::settings::Settings;
+
+// seconds at normal speed
+pub const ROTATE_TIME: f32 = 0.12;
+const MIN_TRAVEL_TIME: f32 = 0.1;
+const MAX_TRAVEL_TIME: f32 = 0.6;
+// pixels per second of a piece moving between the trays and the board
+const TRAVEL_SPEED: f32 = 1500.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum AnimationSpeed {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+    Off,
+}
+
+impl AnimationSpeed {
+    pub const ALL: [AnimationSpeed; 4] = [AnimationSpeed::Slow, AnimationSpeed::Normal, AnimationSpeed::Fast, AnimationSpeed::Off];
+
+    // name in the settings file
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimationSpeed::Slow => "slow",
+            AnimationSpeed::Normal => "normal",
+            AnimationSpeed::Fast => "fast",
+            AnimationSpeed::Off => "off",
+        }
+    }
+
+    pub fn locale_key(self) -> &'static str {
+        match self {
+            AnimationSpeed::Slow => "animation.slow",
+            AnimationSpeed::Normal => "animation.normal",
+            AnimationSpeed::Fast => "animation.fast",
+            AnimationSpeed::Off => "animation.off",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&speed| speed == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // None jumps to the end right away
+    fn factor(self) -> Option<f32> {
+        match self {
+            AnimationSpeed::Slow => Some(0.5),
+            AnimationSpeed::Normal => Some(1.0),
+            AnimationSpeed::Fast => Some(2.0),
+            AnimationSpeed::Off => None,
+        }
+    }
+}
+
+// moves the transform from `start` to `target`, a held piece follows the cursor
+// and only turns
+#[derive(Component)]
+pub struct Tween {
+    start: Transform,
+    pub target: Transform,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl Tween {
+    pub fn new(start: Transform, target: Transform, duration: f32) -> Self {
+        Self { start, target, elapsed: 0.0, duration }
+    }
+
+    // takes longer the farther the piece goes
+    pub fn travel(start: Transform, target: Transform) -> Self {
+        let distance = start.translation.xy().distance(target.translation.xy());
+        Self::new(start, target, (distance / TRAVEL_SPEED).clamp(MIN_TRAVEL_TIME, MAX_TRAVEL_TIME))
+    }
+}
+
+// piece placed on the board on its way there, with the cells it covers as they were before,
+// despawned once it lands
+#[derive(Component)]
+pub struct Landing(pub Vec<(Hex, Cell)>);
+
+// cells shown under the pieces that did not land yet
+pub fn landing_cells(landing: &Query<&Landing>) -> HashMap<Hex, Cell> {
+    landing.iter().flat_map(|landing| landing.0.iter().copied()).collect()
+}
+
+// pose that puts the blocks of the shape on the hexes,
+// of all such poses the one closest to how the piece is turned now
+pub fn placed_transform(shape: usize, hexes: &[Hex], current: &Transform) -> Option<Transform> {
+    let blocks = piece_shape(shape);
+    let targets: HashSet<Hex> = hexes.iter().copied().collect();
+    let mut poses = Vec::new();
+    for turn in 0..6 {
+        for mirror in [1.0, -1.0] {
+            // the first block is at the origin of the piece
+            for hex in hexes {
+                let pose = Transform {
+                    translation: hex_to_pixel(hex).extend(SELECTED_Z),
+                    rotation: Quat::from_rotation_z((turn as f32 * 60.0).to_radians()),
+                    scale: Vec3::new(mirror, 1.0, 1.0),
+                };
+                let covered: HashSet<Hex> = blocks
+                    .iter()
+                    .map(|block| pixel_to_hex(pose.transform_point(hex_to_pixel(block).extend(0.0)).xy()))
+                    .collect();
+                if covered == targets {
+                    poses.push(pose);
+                }
+            }
+        }
+    }
+
+    let turned_by = |pose: &Transform| {
+        let mirrored = pose.scale.x.signum() != current.scale.x.signum();
+        pose.rotation.angle_between(current.rotation) + if mirrored { PI } else { 0.0 }
+    };
+    poses.into_iter().min_by(|a, b| turned_by(a).total_cmp(&turned_by(b)))
+}
+
+pub fn animate_tweens(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut tweens: Query<(Entity, &mut Transform, &mut Tween, Has<Selected>, Has<Landing>)>,
+    mut commands: Commands,
+) {
+    let factor = settings.animation_speed.factor();
+    for (entity, mut transform, mut tween, held, landing) in &mut tweens {
+        tween.elapsed = match factor {
+            Some(factor) => tween.elapsed + time.delta_seconds() * factor,
+            None => tween.duration
+        };
+        let progress = (tween.elapsed / tween.duration).min(1.0);
+        // fast at first, slowing down at the end
+        let eased = 1.0 - (1.0 - progress).powi(3);
+
+        transform.rotation = tween.start.rotation.slerp(tween.target.rotation, eased);
+        transform.scale = tween.start.scale.lerp(tween.target.scale, eased);
+        if !held {
+            transform.translation = tween.start.translation.lerp(tween.target.translation, eased);
+        }
+
+        if progress >= 1.0 {
+            if landing {
+                commands.entity(entity).despawn_recursive();
+            } else {
+                commands.entity(entity).remove::<Tween>();
+            }
+        }
+    }
+}
+
+// any press ends the animations at once so quick play never waits for them,
+// pieces are then picked from where they are going
+pub fn skip_animations(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    touches: Res<Touches>,
+    mut tweens: Query<(&mut Transform, &mut Tween), Without<Selected>>,
+) {
+    let pressed = keys.get_just_pressed().next().is_some()
+        || mouse_buttons.get_just_pressed().next().is_some()
+        || gamepad_buttons.get_just_pressed().next().is_some()
+        || touches.any_just_pressed();
+    if !pressed {
+        return;
+    }
+
+    for (mut transform, mut tween) in &mut tweens {
+        tween.elapsed = tween.duration;
+        *transform = tween.target;
+    }
+}
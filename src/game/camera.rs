@@ -0,0 +1,145 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_3};
+
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use crate::CursorWorldCoords;
+use crate::game::*;
+
+const MIN_ZOOM: f32 = 0.35;
+const MAX_ZOOM: f32 = 1.0;
+// scale change for one line of the scroll wheel
+const ZOOM_STEP: f32 = 1.1;
+const PIXELS_PER_SCROLL_LINE: f32 = 100.0;
+// the view can be moved only inside of the unzoomed view
+const PAN_AREA: Vec2 = Vec2 { x: 1920.0, y: 1080.0 };
+// fraction of the remaining view rotation done per second
+const VIEW_ROTATION_SPEED: f32 = 4.0;
+
+pub fn reset_camera(mut q_camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>) {
+    let (mut transform, mut projection) = q_camera.single_mut();
+    transform.translation = Vec3 { x: 0.0, y: 0.0, z: transform.translation.z };
+    transform.rotation = Quat::IDENTITY;
+    projection.scale = MAX_ZOOM;
+}
+
+// scroll wheel zooms around the cursor when it does not rotate a held piece
+pub fn zoom_camera(
+    mut scroll_evr: EventReader<MouseWheel>,
+    world_cursor: Res<CursorWorldCoords>,
+    selected_piece: Query<(), (With<Piece>, With<Selected>)>,
+    mut q_camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    let scroll: f32 = scroll_evr.read().map(|ev| match ev.unit {
+        MouseScrollUnit::Line => ev.y,
+        MouseScrollUnit::Pixel => ev.y / PIXELS_PER_SCROLL_LINE,
+    }).sum();
+    if scroll == 0.0 || !selected_piece.is_empty() {
+        return;
+    }
+
+    let (mut transform, mut projection) = q_camera.single_mut();
+    let new_scale = (projection.scale * ZOOM_STEP.powf(-scroll)).clamp(MIN_ZOOM, MAX_ZOOM);
+
+    // keep the point under the cursor in place
+    let camera_position = transform.translation.xy();
+    let new_position = world_cursor.0 + (camera_position - world_cursor.0) * new_scale / projection.scale;
+    projection.scale = new_scale;
+    transform.translation = clamp_camera_position(new_position, new_scale, transform.rotation).extend(transform.translation.z);
+}
+
+// middle mouse button or the pan modifier with left mouse button drags the view
+pub fn pan_camera(
+    mut motion_evr: EventReader<MouseMotion>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    actions: Res<ActionState>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut q_camera: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let motion: Vec2 = motion_evr.read().map(|ev| ev.delta).sum();
+    let panning = mouse_buttons.pressed(MouseButton::Middle) ||
+        (actions.pan_modifier_held() && mouse_buttons.pressed(MouseButton::Left));
+    if !panning || motion == Vec2::ZERO {
+        return;
+    }
+
+    let window = q_window.single();
+    let (mut transform, projection) = q_camera.single_mut();
+    let world_units_per_pixel = projection.area.width() / window.width();
+
+    // screen y axis points down
+    let screen_delta = Vec3 { x: -motion.x, y: motion.y, z: 0.0 } * world_units_per_pixel;
+    let new_position = transform.translation.xy() + (transform.rotation * screen_delta).xy();
+    transform.translation = clamp_camera_position(new_position, projection.scale, transform.rotation).extend(transform.translation.z);
+}
+
+// turns the view so the start corner of the current player is at the bottom of the screen
+pub fn rotate_view_to_player(
+    time: Res<Time>,
+    game: Res<Game>,
+    mut q_camera: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+) {
+    let (mut transform, projection) = q_camera.single_mut();
+    let target = Quat::from_rotation_z(target_view_angle(&game));
+    let progress = (VIEW_ROTATION_SPEED * time.delta_seconds()).min(1.0);
+    transform.rotation = transform.rotation.slerp(target, progress);
+    // the pan limits turn along with the view
+    transform.translation = clamp_camera_position(transform.translation.xy(), projection.scale, transform.rotation)
+        .extend(transform.translation.z);
+}
+
+// turn the view ends up with, counterclockwise
+pub fn target_view_angle(game: &Game) -> f32 {
+    match game.player_starts.get(game.current_player) {
+        Some(start) if game.rotate_view => {
+            let start_direction = hex_to_pixel(start);
+            // three player board is symmetric only for 120° turns
+            let step = if game.player_count == 3 { 2.0 * FRAC_PI_3 } else { FRAC_PI_3 };
+            let angle = start_direction.y.atan2(start_direction.x) + FRAC_PI_2;
+            (angle / step).round() * step
+        }
+        _ => 0.0
+    }
+}
+
+// turn of the view right now, counterclockwise
+pub fn view_angle(camera_transform: &GlobalTransform) -> f32 {
+    let (_, rotation, _) = camera_transform.to_scale_rotation_translation();
+    let x_axis = rotation * Vec3::X;
+    x_axis.y.atan2(x_axis.x)
+}
+
+// world size of the view at the default zoom, the camera always shows all of PAN_AREA
+pub fn unzoomed_view_size(window: &Window) -> Vec2 {
+    let aspect = window.width() / window.height();
+    Vec2 { x: PAN_AREA.x.max(PAN_AREA.y * aspect), y: PAN_AREA.y.max(PAN_AREA.x / aspect) }
+}
+
+// PAN_AREA lies along the screen, so the position is clamped as seen through the turned view
+fn clamp_camera_position(position: Vec2, scale: f32, rotation: Quat) -> Vec2 {
+    let limit = PAN_AREA / 2.0 * (1.0 - scale);
+    let on_screen = (rotation.inverse() * position.extend(0.0)).xy().clamp(-limit, limit);
+    (rotation * on_screen.extend(0.0)).xy()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pan_limits_follow_the_turned_view() {
+        let scale = 0.5;
+        let limit = PAN_AREA / 2.0 * (1.0 - scale);
+        let rotation = Quat::from_rotation_z(FRAC_PI_3);
+        let along_screen = |x: f32, y: f32| (rotation * Vec3 { x, y, z: 0.0 }).xy();
+
+        // the wide side of the area stays along the width of the screen
+        let clamped = clamp_camera_position(along_screen(10_000.0, 0.0), scale, rotation);
+        assert!(clamped.distance(along_screen(limit.x, 0.0)) < 0.01, "{clamped:?}");
+        let clamped = clamp_camera_position(along_screen(0.0, -10_000.0), scale, rotation);
+        assert!(clamped.distance(along_screen(0.0, -limit.y)) < 0.01, "{clamped:?}");
+
+        let inside = along_screen(limit.x * 0.9, limit.y * 0.9);
+        assert!(clamp_camera_position(inside, scale, rotation).distance(inside) < 0.01);
+    }
+}
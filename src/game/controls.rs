@@ -0,0 +1,332 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use crate::game::*;
+use crate::settings::Settings;
+
+// inputs that can be bound to each action
+pub const BINDING_SLOTS: usize = 2;
+// held with the left mouse button it drags the view, so it is never bound to an action
+pub const PAN_MODIFIER: [KeyCode; 2] = [KeyCode::ControlLeft, KeyCode::ControlRight];
+// keys the keyboard cursor and the piece cycling always use, with or without shift
+const FIXED_KEYS: [KeyCode; 11] = [
+    KeyCode::KeyW, KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD, KeyCode::KeyQ, KeyCode::KeyE,
+    KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::Tab,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    RotateCw,
+    RotateCcw,
+    Flip,
+    Pick,
+    Drop,
+    Cancel,
+    Pass,
+    Undo,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::RotateCw,
+        Action::RotateCcw,
+        Action::Flip,
+        Action::Pick,
+        Action::Drop,
+        Action::Cancel,
+        Action::Pass,
+        Action::Undo,
+    ];
+
+    // key of the name shown in the menu in the locale files
+    pub fn locale_key(self) -> &'static str {
+        match self {
+            Action::RotateCw => "action.rotate_cw",
+            Action::RotateCcw => "action.rotate_ccw",
+            Action::Flip => "action.flip",
+            Action::Pick => "action.pick",
+            Action::Drop => "action.drop",
+            Action::Cancel => "action.cancel",
+            Action::Pass => "action.pass",
+            Action::Undo => "action.undo",
+        }
+    }
+
+    // key of the action in the settings file
+    pub fn config_key(self) -> &'static str {
+        match self {
+            Action::RotateCw => "bind_rotate_cw",
+            Action::RotateCcw => "bind_rotate_ccw",
+            Action::Flip => "bind_flip",
+            Action::Pick => "bind_pick",
+            Action::Drop => "bind_drop",
+            Action::Cancel => "bind_cancel",
+            Action::Pass => "bind_pass",
+            Action::Undo => "bind_undo",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    // the key without shift
+    Key(KeyCode),
+    ShiftKey(KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+}
+
+// keys that can be bound, with the names shown in the menu and written to the settings file in every language
+const KEY_NAMES: [(KeyCode, &str); 55] = [
+    (KeyCode::KeyA, "A"), (KeyCode::KeyB, "B"), (KeyCode::KeyC, "C"), (KeyCode::KeyD, "D"),
+    (KeyCode::KeyE, "E"), (KeyCode::KeyF, "F"), (KeyCode::KeyG, "G"), (KeyCode::KeyH, "H"),
+    (KeyCode::KeyI, "I"), (KeyCode::KeyJ, "J"), (KeyCode::KeyK, "K"), (KeyCode::KeyL, "L"),
+    (KeyCode::KeyM, "M"), (KeyCode::KeyN, "N"), (KeyCode::KeyO, "O"), (KeyCode::KeyP, "P"),
+    (KeyCode::KeyQ, "Q"), (KeyCode::KeyR, "R"), (KeyCode::KeyS, "S"), (KeyCode::KeyT, "T"),
+    (KeyCode::KeyU, "U"), (KeyCode::KeyV, "V"), (KeyCode::KeyW, "W"), (KeyCode::KeyX, "X"),
+    (KeyCode::KeyY, "Y"), (KeyCode::KeyZ, "Z"),
+    (KeyCode::Digit0, "0"), (KeyCode::Digit1, "1"), (KeyCode::Digit2, "2"), (KeyCode::Digit3, "3"),
+    (KeyCode::Digit4, "4"), (KeyCode::Digit5, "5"), (KeyCode::Digit6, "6"), (KeyCode::Digit7, "7"),
+    (KeyCode::Digit8, "8"), (KeyCode::Digit9, "9"),
+    (KeyCode::Space, "Space"), (KeyCode::Enter, "Enter"), (KeyCode::Escape, "Escape"), (KeyCode::Tab, "Tab"),
+    (KeyCode::Backspace, "Backspace"), (KeyCode::Delete, "Delete"), (KeyCode::Insert, "Insert"),
+    (KeyCode::Home, "Home"), (KeyCode::End, "End"), (KeyCode::PageUp, "Page up"), (KeyCode::PageDown, "Page down"),
+    (KeyCode::ArrowUp, "Up"), (KeyCode::ArrowDown, "Down"), (KeyCode::ArrowLeft, "Left"), (KeyCode::ArrowRight, "Right"),
+    (KeyCode::AltLeft, "Alt"), (KeyCode::Comma, ","), (KeyCode::Period, "."),
+    (KeyCode::Slash, "/"),
+];
+
+const SHIFT_PREFIX: &str = "Shift+";
+
+impl Binding {
+    // None for keys missing in KEY_NAMES, shift itself is only a modifier
+    pub fn from_key(key: KeyCode, shift: bool) -> Option<Self> {
+        KEY_NAMES.iter().any(|&(known, _)| known == key)
+            .then_some(if shift { Binding::ShiftKey(key) } else { Binding::Key(key) })
+    }
+
+    // keys that already do something in the game whatever is bound to them
+    pub fn is_fixed(self) -> bool {
+        match self {
+            Binding::Key(key) | Binding::ShiftKey(key) => FIXED_KEYS.contains(&key),
+            _ => false
+        }
+    }
+
+    pub fn name(self) -> String {
+        let key_name = |key| KEY_NAMES.iter().find(|&&(known, _)| known == key).map_or("?", |&(_, name)| name);
+        match self {
+            Binding::Key(key) => key_name(key).to_string(),
+            Binding::ShiftKey(key) => format!("{SHIFT_PREFIX}{}", key_name(key)),
+            Binding::Mouse(MouseButton::Left) => "Left click".to_string(),
+            Binding::Mouse(MouseButton::Right) => "Right click".to_string(),
+            Binding::Mouse(MouseButton::Middle) => "Middle click".to_string(),
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::WheelUp => "Wheel up".to_string(),
+            Binding::WheelDown => "Wheel down".to_string(),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let key_by_name = |name| KEY_NAMES.iter().find(|&&(_, known)| known == name).map(|&(key, _)| key);
+        match name {
+            "Left click" => Some(Binding::Mouse(MouseButton::Left)),
+            "Right click" => Some(Binding::Mouse(MouseButton::Right)),
+            "Middle click" => Some(Binding::Mouse(MouseButton::Middle)),
+            "Wheel up" => Some(Binding::WheelUp),
+            "Wheel down" => Some(Binding::WheelDown),
+            _ => match name.strip_prefix(SHIFT_PREFIX) {
+                Some(key) => key_by_name(key).map(Binding::ShiftKey),
+                None => key_by_name(name).map(Binding::Key),
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bindings([[Option<Binding>; BINDING_SLOTS]; Action::ALL.len()]);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut bindings = Self([[None; BINDING_SLOTS]; Action::ALL.len()]);
+        bindings.0[Action::RotateCw as usize] = [Some(Binding::Key(KeyCode::KeyR)), Some(Binding::WheelDown)];
+        bindings.0[Action::RotateCcw as usize] = [Some(Binding::ShiftKey(KeyCode::KeyR)), Some(Binding::WheelUp)];
+        bindings.0[Action::Flip as usize] = [Some(Binding::Key(KeyCode::KeyF)), None];
+        bindings.0[Action::Pick as usize] = [Some(Binding::Mouse(MouseButton::Left)), None];
+        bindings.0[Action::Drop as usize] = [Some(Binding::Key(KeyCode::Enter)), Some(Binding::Key(KeyCode::Space))];
+        bindings.0[Action::Cancel as usize] = [Some(Binding::Key(KeyCode::Escape)), None];
+        bindings.0[Action::Pass as usize] = [Some(Binding::Key(KeyCode::KeyP)), None];
+        bindings.0[Action::Undo as usize] = [Some(Binding::Key(KeyCode::KeyU)), None];
+        bindings
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, action: Action) -> [Option<Binding>; BINDING_SLOTS] {
+        self.0[action as usize]
+    }
+
+    pub fn set(&mut self, action: Action, slot: usize, binding: Option<Binding>) {
+        self.0[action as usize][slot] = binding;
+    }
+
+    // an input bound to another slot takes over what this slot had, so no input triggers two actions
+    pub fn assign(&mut self, action: Action, slot: usize, binding: Option<Binding>) {
+        let previous = self.get(action)[slot];
+        if binding.is_some() {
+            for other in self.0.iter_mut().flatten().filter(|other| **other == binding) {
+                *other = previous;
+            }
+        }
+        self.set(action, slot, binding);
+    }
+
+    // comma separated names as in the settings file, unknown names leave the slot empty
+    pub fn parse_slots(value: &str) -> [Option<Binding>; BINDING_SLOTS] {
+        let mut slots = [None; BINDING_SLOTS];
+        for (slot, name) in slots.iter_mut().zip(value.split(',')) {
+            *slot = Binding::parse(name.trim());
+        }
+        slots
+    }
+
+    pub fn slots_to_string(&self, action: Action) -> String {
+        self.get(action).iter().flatten().map(|binding| binding.name()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+// actions whose bound inputs changed this frame
+#[derive(Resource, Default)]
+pub struct ActionState {
+    just_pressed: HashSet<Action>,
+    just_released: HashSet<Action>,
+    pan_modifier: bool,
+}
+
+impl ActionState {
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    pub fn pan_modifier_held(&self) -> bool {
+        self.pan_modifier
+    }
+}
+
+pub fn update_action_state(
+    settings: Res<Settings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut scroll_evr: EventReader<MouseWheel>,
+    mut actions: ResMut<ActionState>,
+) {
+    let scroll: f32 = scroll_evr.read().map(|ev| ev.y).sum();
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    actions.just_pressed.clear();
+    actions.just_released.clear();
+    actions.pan_modifier = keys.any_pressed(PAN_MODIFIER);
+
+    for action in Action::ALL {
+        for binding in settings.bindings.get(action).into_iter().flatten() {
+            // a wheel step is pressed and released at once
+            let (pressed, released) = match binding {
+                Binding::Key(key) => (keys.just_pressed(key) && !shift, keys.just_released(key)),
+                Binding::ShiftKey(key) => (keys.just_pressed(key) && shift, keys.just_released(key)),
+                Binding::Mouse(button) => (mouse_buttons.just_pressed(button), mouse_buttons.just_released(button)),
+                Binding::WheelUp => (scroll > 0.0, scroll > 0.0),
+                Binding::WheelDown => (scroll < 0.0, scroll < 0.0),
+            };
+            if pressed {
+                actions.just_pressed.insert(action);
+            }
+            if released {
+                actions.just_released.insert(action);
+            }
+        }
+    }
+}
+
+pub fn pass_turn_action(
+    actions: Res<ActionState>,
+    selected_piece: Query<Entity, (With<Piece>, With<Selected>)>,
+    mut ev_pass: EventWriter<PassTurnEvent>,
+) {
+    if actions.just_pressed(Action::Pass) && selected_piece.is_empty() {
+        ev_pass.send(PassTurnEvent);
+    }
+}
+
+pub fn undo_action(
+    actions: Res<ActionState>,
+    selected_piece: Query<Entity, (With<Piece>, With<Selected>)>,
+    mut ev_undo: EventWriter<UndoEvent>,
+) {
+    if actions.just_pressed(Action::Undo) && selected_piece.is_empty() {
+        ev_undo.send(UndoEvent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_names_parse_back() {
+        let bindings = KEY_NAMES.iter()
+            .flat_map(|&(key, _)| [Binding::Key(key), Binding::ShiftKey(key)])
+            .chain([MouseButton::Left, MouseButton::Right, MouseButton::Middle].map(Binding::Mouse))
+            .chain([Binding::WheelUp, Binding::WheelDown]);
+        for binding in bindings {
+            assert_eq!(Binding::parse(&binding.name()), Some(binding));
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_not_bindings() {
+        for name in ["", "Shift+", "Shift+Shift+A", "a", "F13", "Ctrl", "Mouse Back"] {
+            assert_eq!(Binding::parse(name), None, "{name:?}");
+        }
+        // ctrl is kept for panning
+        assert_eq!(Binding::from_key(KeyCode::ControlLeft, false), None);
+    }
+
+    #[test]
+    fn slots_parse_from_the_settings_file() {
+        let slots = Bindings::parse_slots("Shift+R, Wheel up");
+        assert_eq!(slots, [Some(Binding::ShiftKey(KeyCode::KeyR)), Some(Binding::WheelUp)]);
+        assert_eq!(Bindings::parse_slots("nothing, Enter"), [None, Some(Binding::Key(KeyCode::Enter))]);
+
+        let bindings = Bindings::default();
+        for action in Action::ALL {
+            let slots = Bindings::parse_slots(&bindings.slots_to_string(action));
+            // empty slots come last in the file
+            assert_eq!(slots.iter().flatten().collect::<Vec<_>>(), bindings.get(action).iter().flatten().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn a_binding_in_use_swaps_slots() {
+        let mut bindings = Bindings::default();
+        let flip = bindings.get(Action::Flip)[0];
+        bindings.assign(Action::Pass, 0, flip);
+        assert_eq!(bindings.get(Action::Pass)[0], flip);
+        assert_eq!(bindings.get(Action::Flip)[0], Some(Binding::Key(KeyCode::KeyP)));
+
+        // moved to the other slot of the same action
+        bindings.assign(Action::Pass, 1, flip);
+        assert_eq!(bindings.get(Action::Pass), [None, flip]);
+    }
+
+    #[test]
+    fn movement_keys_are_fixed() {
+        assert!(Binding::Key(KeyCode::KeyW).is_fixed());
+        assert!(Binding::ShiftKey(KeyCode::Tab).is_fixed());
+        assert!(!Binding::Key(KeyCode::KeyR).is_fixed());
+        assert!(!Binding::Mouse(MouseButton::Left).is_fixed());
+    }
+}
@@ -0,0 +1,137 @@
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+// clients broadcast a query on all of these ports, servers answer with their game
+// on the first one not taken by another server on the same machine
+const DISCOVERY_PORTS: RangeInclusive<u16> = 7778..=7781;
+const DISCOVERY_QUERY: &str = "gemblo-discover";
+const DISCOVERY_ANSWER: &str = "gemblo-game";
+const QUERY_INTERVAL: Duration = Duration::from_secs(1);
+// games not answering for this long are removed from the list
+const GAME_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LanGame {
+    pub address: SocketAddr,
+    pub player_count: usize,
+    pub free_seats: usize,
+    last_seen: Instant,
+}
+
+impl LanGame {
+    // key of the board name in the locale files
+    pub fn board_locale_key(&self) -> &'static str {
+        match self.player_count {
+            3 => "board.three",
+            5 | 6 => "board.six",
+            _ => "board.four"
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct LanDiscovery {
+    socket: UdpSocket,
+    last_query: Option<Instant>,
+    pub games: Vec<LanGame>,
+    // game that turned us away for having no free seat
+    pub full_game: Option<SocketAddr>,
+}
+
+// answers queries until the process ends, `free_seats` is updated by the server
+pub fn spawn_discovery_responder(tcp_address: SocketAddr, player_count: usize, free_seats: Arc<AtomicUsize>) {
+    let tcp_port = tcp_address.port();
+    // other machines can not join a server listening on the loopback interface
+    let local_only = tcp_address.ip().is_loopback();
+
+    let Some(socket) = DISCOVERY_PORTS.filter_map(|port| UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).ok()).next() else {
+        println!("LAN discovery is disabled, ports {DISCOVERY_PORTS:?} are all taken");
+        return;
+    };
+
+    thread::spawn(move || {
+        let mut buffer = [0; 256];
+        while let Ok((length, sender)) = socket.recv_from(&mut buffer) {
+            if &buffer[..length] != DISCOVERY_QUERY.as_bytes() || (local_only && !sender.ip().is_loopback()) {
+                continue;
+            }
+            let answer = format!("{DISCOVERY_ANSWER} {tcp_port} {player_count} {}", free_seats.load(Ordering::Relaxed));
+            let _ = socket.send_to(answer.as_bytes(), sender);
+        }
+    });
+}
+
+pub fn start_discovery(mut commands: Commands) {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.set_broadcast(true)?;
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        });
+
+    match socket {
+        Ok(socket) => commands.insert_resource(LanDiscovery { socket, last_query: None, games: Vec::new(), full_game: None }),
+        Err(error) => println!("Failed to start LAN discovery: {error}")
+    }
+}
+
+pub fn stop_discovery(mut commands: Commands) {
+    commands.remove_resource::<LanDiscovery>();
+}
+
+pub fn discover_lan_games(discovery: Option<ResMut<LanDiscovery>>) {
+    let Some(mut discovery) = discovery else {
+        return;
+    };
+    let now = Instant::now();
+
+    if discovery.last_query.is_none_or(|last_query| now - last_query >= QUERY_INTERVAL) {
+        // broadcasts do not always come back to the same machine
+        for target in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+            for port in DISCOVERY_PORTS {
+                let _ = discovery.socket.send_to(DISCOVERY_QUERY.as_bytes(), (target, port));
+            }
+        }
+        discovery.last_query = Some(now);
+    }
+
+    let mut buffer = [0; 256];
+    while let Ok((length, sender)) = discovery.socket.recv_from(&mut buffer) {
+        let Some(game) = parse_answer(&buffer[..length], sender, now) else {
+            continue;
+        };
+
+        // a server on this machine answers both the broadcast and the loopback query
+        let same_server = |known: &LanGame| known.address == game.address ||
+            (known.address.port() == game.address.port() && (known.address.ip().is_loopback() || game.address.ip().is_loopback()));
+        match discovery.games.iter_mut().find(|known| same_server(known)) {
+            Some(known) if game.address.ip().is_loopback() => known.last_seen = now,
+            Some(known) => *known = game,
+            None => discovery.games.push(game)
+        }
+    }
+
+    discovery.games.retain(|game| now - game.last_seen < GAME_TIMEOUT);
+}
+
+fn parse_answer(answer: &[u8], sender: SocketAddr, now: Instant) -> Option<LanGame> {
+    let answer = std::str::from_utf8(answer).ok()?;
+    let mut words = answer.split_whitespace();
+    if words.next()? != DISCOVERY_ANSWER {
+        return None;
+    }
+
+    let tcp_port: u16 = words.next()?.parse().ok()?;
+    Some(LanGame {
+        address: SocketAddr::new(sender.ip(), tcp_port),
+        player_count: words.next()?.parse().ok()?,
+        free_seats: words.next()?.parse().ok()?,
+        last_seen: now,
+    })
+}
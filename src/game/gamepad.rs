@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use crate::game::*;
+use crate::game::keyboard::{cycle_held_piece, move_hex_cursor};
+
+const STICK_DEADZONE: f32 = 0.5;
+// delay between cursor steps while the stick is held
+const STICK_REPEAT_SECONDS: f32 = 0.18;
+
+pub fn gamepad_select_piece(
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mut game: ResMut<Game>,
+    mut pieces: Query<(Entity, &mut Transform, &Children, &PlayerIndex, Has<Selected>), With<Piece>>,
+    mut commands: Commands,
+) {
+    for gamepad in gamepads.iter() {
+        if buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::RightTrigger)) {
+            cycle_held_piece(&mut game, &mut pieces, &mut commands, false);
+        } else if buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::LeftTrigger)) {
+            cycle_held_piece(&mut game, &mut pieces, &mut commands, true);
+        }
+    }
+}
+
+pub fn gamepad_move_cursor(
+    time: Res<Time>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut game: ResMut<Game>,
+    q_camera: Query<&GlobalTransform, With<Camera>>,
+    mut repeat_timer: Local<f32>,
+) {
+    if game.hex_cursor.is_none() {
+        return;
+    }
+
+    let stick = gamepads
+        .iter()
+        .map(|gamepad| Vec2 {
+            x: axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.0),
+            y: axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.0),
+        })
+        .find(|stick| stick.length() > STICK_DEADZONE);
+
+    let Some(stick) = stick else {
+        *repeat_timer = 0.0;
+        return;
+    };
+
+    *repeat_timer -= time.delta_seconds();
+    if *repeat_timer > 0.0 {
+        return;
+    }
+    *repeat_timer = STICK_REPEAT_SECONDS;
+
+    // neighbour that points closest to the stick direction on the screen
+    let (_, camera_rotation, _) = q_camera.single().to_scale_rotation_translation();
+    let stick = (camera_rotation * stick.extend(0.0)).xy();
+    let direction = NEIGHBOURS
+        .into_iter()
+        .max_by(|a, b| {
+            let a = hex_to_pixel(a).normalize().dot(stick);
+            let b = hex_to_pixel(b).normalize().dot(stick);
+            a.total_cmp(&b)
+        })
+        .unwrap();
+    move_hex_cursor(&mut game, direction);
+}
+
+// West and triggers rotate, North flips, South places, East cancels or passes when nothing is held
+pub fn gamepad_buttons(
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    game: Res<Game>,
+    selected_piece: Query<Entity, (With<Piece>, With<Selected>)>,
+    mut ev_control: EventWriter<PieceControlEvent>,
+    mut ev_pass: EventWriter<PassTurnEvent>,
+) {
+    for gamepad in gamepads.iter() {
+        let just_pressed = |button_type| buttons.just_pressed(GamepadButton::new(gamepad, button_type));
+
+        if game.hex_cursor.is_none() {
+            if just_pressed(GamepadButtonType::East) && selected_piece.is_empty() {
+                ev_pass.send(PassTurnEvent);
+            }
+            continue;
+        }
+
+        if just_pressed(GamepadButtonType::West) || just_pressed(GamepadButtonType::RightTrigger2) {
+            ev_control.send(PieceControlEvent::RotateCw);
+        }
+        if just_pressed(GamepadButtonType::LeftTrigger2) {
+            ev_control.send(PieceControlEvent::RotateCcw);
+        }
+        if just_pressed(GamepadButtonType::North) {
+            ev_control.send(PieceControlEvent::Flip);
+        }
+        if just_pressed(GamepadButtonType::South) {
+            ev_control.send(PieceControlEvent::Place);
+        }
+        if just_pressed(GamepadButtonType::East) {
+            ev_control.send(PieceControlEvent::Cancel);
+        }
+    }
+}
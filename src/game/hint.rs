@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use crate::hex::Hex;
+use crate::game::*;
+use crate::game::setup::ALL_PIECES;
+
+const HINT_PULSE_SPEED: f32 = 6.0;
+
+pub fn on_hint(
+    mut ev_hint: EventReader<HintEvent>,
+    mut game: ResMut<Game>,
+    pieces: Query<(Entity, &GlobalTransform, &Children, &Piece, &PlayerIndex)>,
+    blocks: Query<&GlobalTransform, With<BlockSelectable>>,
+    mut hinted_blocks: Query<(Entity, &mut Sprite), With<Hinted>>,
+    mut commands: Commands,
+) {
+    if ev_hint.read().count() == 0 {
+        return;
+    }
+
+    let current_player = game.current_player;
+    let index = match &game.hint {
+        Some(hint) if hint.player == current_player => hint.index + 1,
+        _ => 0
+    };
+
+    // larger pieces first, they are harder to place later
+    let mut player_pieces: Vec<(Entity, &GlobalTransform, &Children, usize)> = pieces
+        .iter()
+        .filter(|(_, _, _, _, &PlayerIndex(player_index))| player_index == current_player)
+        .map(|(entity, transform, children, &Piece(size), _)| (entity, transform, children, size))
+        .collect();
+    player_pieces.sort_by_key(|&(entity, _, _, size)| (std::cmp::Reverse(size), entity));
+
+    let mut suggestions: Vec<(&Children, Vec<Hex>)> = Vec::new();
+    for (_, piece_transform, children, _) in player_pieces {
+        let piece_blocks: Vec<Hex> = children
+            .iter()
+            .filter_map(|child| blocks.get(*child).ok())
+            .map(|transform| pixel_to_hex(transform.translation().xy() - piece_transform.translation().xy()))
+            .collect();
+
+        for placement in legal_placements(&game.board, &piece_blocks, current_player) {
+            suggestions.push((children, placement));
+        }
+    }
+
+    clear_hinted_blocks(&mut commands, &mut hinted_blocks);
+
+    let hexes = if suggestions.is_empty() {
+        Vec::new()
+    } else {
+        let (children, hexes) = suggestions.swap_remove(index % suggestions.len());
+        for child in children.iter() {
+            commands.entity(*child).insert(Hinted);
+        }
+        hexes
+    };
+
+    game.hint = Some(Hint { player: current_player, index, hexes });
+}
+
+pub fn clear_stale_hint(
+    mut game: ResMut<Game>,
+    mut hinted_blocks: Query<(Entity, &mut Sprite), With<Hinted>>,
+    mut commands: Commands,
+) {
+    let stale = matches!(&game.hint, Some(hint) if hint.player != game.current_player);
+    if stale {
+        clear_hinted_blocks(&mut commands, &mut hinted_blocks);
+        game.hint = None;
+    }
+}
+
+pub fn pulse_hinted_blocks(
+    time: Res<Time>,
+    mut hinted_blocks: Query<&mut Sprite, (With<Hinted>, Without<Selected>)>,
+) {
+    let alpha = 0.7 + 0.3 * (time.elapsed_seconds() * HINT_PULSE_SPEED).sin();
+    for mut sprite in &mut hinted_blocks {
+        sprite.color = sprite.color.with_a(alpha);
+    }
+}
+
+fn clear_hinted_blocks(commands: &mut Commands, hinted_blocks: &mut Query<(Entity, &mut Sprite), With<Hinted>>) {
+    for (block, mut sprite) in hinted_blocks.iter_mut() {
+        sprite.color = sprite.color.with_a(1.0);
+        commands.entity(block).remove::<Hinted>();
+    }
+}
+
+// whether any of the shapes left to the player fits somewhere on the board
+pub fn has_legal_placement(board: &Board, player_index: usize, mut shapes: impl Iterator<Item = usize>) -> bool {
+    shapes.any(|shape| {
+        let piece_blocks: Vec<Hex> = ALL_PIECES[shape].iter().map(|&(q, r)| Hex { q, r }).collect();
+        !legal_placements(board, &piece_blocks, player_index).is_empty()
+    })
+}
+
+// every distinct set of hexes the piece can cover, trying each rotation of both sides
+// and each block of the piece on every cell where the player can start a placement
+pub fn legal_placements(board: &Board, piece_blocks: &[Hex], player_index: usize) -> Vec<Vec<Hex>> {
+    let mut seen: HashSet<Vec<Hex>> = HashSet::new();
+    let mut placements = Vec::new();
+    let flipped_blocks: Vec<Hex> = piece_blocks.iter().map(|block| block.reflect()).collect();
+
+    for corner in corner_cells(board, player_index) {
+        for (blocks, rotation) in [piece_blocks, &flipped_blocks].into_iter().flat_map(|blocks|
+            ALL_ROTATIONS.into_iter().map(move |rotation| (blocks, rotation))
+        ) {
+            let rotated_blocks: Vec<Hex> = blocks.iter().map(|block| block.rotate(rotation)).collect();
+
+            for &starting_block in &rotated_blocks {
+                let mut placed_blocks: Vec<Hex> =
+                    rotated_blocks.iter().map(|&block| block - starting_block + corner).collect();
+
+                if !placed_blocks.iter().all(|hex| is_hex_free(board, hex)) ||
+                    !piece_can_be_placed_on_board(board, &placed_blocks, player_index) {
+                    continue;
+                }
+
+                placed_blocks.sort_by_key(|hex| (hex.q, hex.r));
+                if seen.insert(placed_blocks.clone()) {
+                    placements.push(placed_blocks);
+                }
+            }
+        }
+    }
+
+    placements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hexes at most `radius` steps from the origin, where player 0 starts
+    fn round_board(radius: i32) -> Board {
+        let mut board = Board::new();
+        for q in -radius..=radius {
+            for r in (-radius).max(-q - radius)..=radius.min(radius - q) {
+                board.insert(Hex { q, r }, Cell::Empty);
+            }
+        }
+        board.insert(Hex { q: 0, r: 0 }, Cell::PlayerStart(0));
+        board
+    }
+
+    fn line(length: i32) -> Vec<Hex> {
+        (0..length).map(|q| Hex { q, r: 0 }).collect()
+    }
+
+    #[test]
+    fn first_move_covers_the_start_corner() {
+        let board = round_board(4);
+        let placements = legal_placements(&board, &line(3), 0);
+        assert!(!placements.is_empty());
+        assert!(placements.iter().all(|placement| placement.contains(&Hex { q: 0, r: 0 })));
+        // a player without a start corner or blocks on the board has nowhere to go
+        assert!(legal_placements(&board, &line(3), 1).is_empty());
+    }
+
+    #[test]
+    fn placements_touch_own_blocks_only_at_corners() {
+        let mut board = round_board(5);
+        for hex in line(2) {
+            board.insert(hex, Cell::Player(0));
+        }
+        let placements = legal_placements(&board, &line(3), 0);
+        assert!(!placements.is_empty());
+        for placement in &placements {
+            for &hex in placement {
+                assert_eq!(board.get(&hex), Some(&Cell::Empty), "{placement:?} overlaps");
+                assert!(NEIGHBOURS.into_iter().all(|n| !is_hex_belong_to_player(&board, hex + n, 0)), "{placement:?} touches an edge");
+            }
+            assert!(placement.iter().any(|&hex|
+                DIAGONAL_NEIGHBOURS.into_iter().any(|(diagonal, _, _)| is_hex_belong_to_player(&board, hex + diagonal, 0))
+            ));
+        }
+    }
+
+    #[test]
+    fn no_placements_without_a_free_corner() {
+        // the only block of the player is walled in past its edge neighbours
+        let mut board = round_board(3);
+        board.insert(Hex { q: 0, r: 0 }, Cell::Player(0));
+        for (&hex, cell) in board.iter_mut() {
+            let distance = hex.q.abs().max(hex.r.abs()).max((hex.q + hex.r).abs());
+            if distance >= 2 {
+                *cell = Cell::Disabled;
+            }
+        }
+        assert!(legal_placements(&board, &[Hex { q: 0, r: 0 }], 0).is_empty());
+        assert!(legal_placements(&board, &line(2), 0).is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use std::f32::consts::FRAC_PI_3;
+
+use bevy::prelude::*;
+use crate::hex::Hex;
+use crate::game::*;
+
+// W - up, S - down, Q - up-left, E - up-right, A - down-left, D - down-right
+const HEX_DIRECTION_KEYS: [(KeyCode, Hex); 6] = [
+    (KeyCode::KeyW, Hex { q:  0, r: -1 }),
+    (KeyCode::KeyS, Hex { q:  0, r:  1 }),
+    (KeyCode::KeyQ, Hex { q: -1, r:  0 }),
+    (KeyCode::KeyE, Hex { q:  1, r: -1 }),
+    (KeyCode::KeyA, Hex { q: -1, r:  1 }),
+    (KeyCode::KeyD, Hex { q:  1, r:  0 }),
+];
+
+pub fn keyboard_select_piece(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut game: ResMut<Game>,
+    mut pieces: Query<(Entity, &mut Transform, &Children, &PlayerIndex, Has<Selected>), With<Piece>>,
+    mut commands: Commands,
+) {
+    if keys.just_pressed(KeyCode::Tab) {
+        cycle_held_piece(&mut game, &mut pieces, &mut commands, keys.pressed(KeyCode::ShiftLeft));
+    }
+}
+
+// takes the next piece of the current player, putting back the one held
+pub fn cycle_held_piece(
+    game: &mut Game,
+    pieces: &mut Query<(Entity, &mut Transform, &Children, &PlayerIndex, Has<Selected>), With<Piece>>,
+    commands: &mut Commands,
+    backwards: bool,
+) {
+    let mouse_is_dragging = game.hex_cursor.is_none() && pieces.iter().any(|(_, _, _, _, selected)| selected);
+    if mouse_is_dragging || !game.is_local_turn() {
+        return;
+    }
+
+    let mut player_pieces: Vec<Entity> = pieces
+        .iter()
+        .filter(|(_, _, _, &PlayerIndex(player_index), _)| player_index == game.current_player)
+        .map(|(entity, _, _, _, _)| entity)
+        .collect();
+    if player_pieces.is_empty() {
+        return;
+    }
+    player_pieces.sort();
+
+    let held_piece = pieces.iter().find(|(_, _, _, _, selected)| *selected).map(|(entity, _, _, _, _)| entity);
+    let next_index = match held_piece.and_then(|held| player_pieces.iter().position(|&entity| entity == held)) {
+        Some(index) if backwards => (index + player_pieces.len() - 1) % player_pieces.len(),
+        Some(index) => (index + 1) % player_pieces.len(),
+        None => 0
+    };
+
+    if let Some(held) = held_piece {
+        if let Ok((_, transform, children, _, _)) = pieces.get(held) {
+            commands.entity(held).remove::<Selected>().insert(Tween::travel(*transform, game.original_transform));
+            for child in children.iter() {
+                commands.entity(*child).remove::<Selected>();
+            }
+        }
+    }
+
+    let next_piece = player_pieces[next_index];
+    if let Ok((_, transform, children, _, _)) = pieces.get(next_piece) {
+        game.original_transform = *transform;
+        commands.entity(next_piece).insert(Selected);
+        for child in children.iter() {
+            commands.entity(*child).insert(Selected);
+        }
+    }
+
+    if game.hex_cursor.is_none() {
+        let starting_cursor = corner_cells(&game.board, game.current_player)
+            .into_iter()
+            .min_by_key(|hex| (hex.q, hex.r))
+            .unwrap_or(Hex { q: 0, r: 0 });
+        game.hex_cursor = Some(starting_cursor);
+    }
+}
+
+// cursor never leaves the board
+pub fn move_hex_cursor(game: &mut Game, direction: Hex) {
+    if let Some(cursor) = game.hex_cursor {
+        if game.board.contains_key(&(cursor + direction)) {
+            game.hex_cursor = Some(cursor + direction);
+        }
+    }
+}
+
+// directions are on the screen, which turns with the view in 60° steps
+pub fn keyboard_move_cursor(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut game: ResMut<Game>,
+    q_camera: Query<&GlobalTransform, With<Camera>>,
+) {
+    let Some(cursor) = game.hex_cursor else {
+        return;
+    };
+    let view_rotation = Quat::from_rotation_z((view_angle(q_camera.single()) / FRAC_PI_3).round() * FRAC_PI_3);
+    let turn = |rotation: Quat, hex: Hex| pixel_to_hex((rotation * hex_to_pixel(&hex).extend(0.0)).xy());
+
+    // columns are shifted by half a hex, so moving sideways alternates up and down
+    let column_is_even = turn(view_rotation.inverse(), cursor).q.rem_euclid(2) == 0;
+    let arrow_keys = [
+        (KeyCode::ArrowUp, Hex { q: 0, r: -1 }),
+        (KeyCode::ArrowDown, Hex { q: 0, r: 1 }),
+        (KeyCode::ArrowLeft, if column_is_even { Hex { q: -1, r: 0 } } else { Hex { q: -1, r: 1 } }),
+        (KeyCode::ArrowRight, if column_is_even { Hex { q: 1, r: -1 } } else { Hex { q: 1, r: 0 } }),
+    ];
+
+    for (key, direction) in arrow_keys.into_iter().chain(HEX_DIRECTION_KEYS) {
+        if keys.just_pressed(key) {
+            move_hex_cursor(&mut game, turn(view_rotation, direction));
+            return;
+        }
+    }
+}
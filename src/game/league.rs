@@ -0,0 +1,259 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use crate::game::*;
+use crate::settings::config_dir;
+
+// final rankings of finished matches are appended to this file in the config directory
+const RESULTS_FILE: &str = "match_results.txt";
+// and every finished game, played in a match or not, to this one
+const GAMES_FILE: &str = "game_records.txt";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MatchFormat {
+    // rematches until the players go back to the menu
+    Open,
+    Series { games: usize },
+    // every pair of participants plays one two player game
+    League,
+}
+
+// one played game, indexed by seat
+pub struct GameRecord {
+    pub participants: Vec<usize>,
+    pub remaining_blocks: Vec<usize>,
+    pub points: Vec<f32>,
+}
+
+pub struct Standing {
+    pub participant: usize,
+    pub games: usize,
+    pub wins: usize,
+    pub points: f32,
+    pub remaining_blocks: usize,
+}
+
+#[derive(Resource)]
+pub struct Match {
+    pub format: MatchFormat,
+    pub players: Vec<PlayerProfile>,
+    pub palette: ColorPalette,
+    // participants of every game in seat order, empty for an open match
+    fixtures: Vec<Vec<usize>>,
+    pub games: Vec<GameRecord>,
+}
+
+impl Match {
+    pub fn new(format: MatchFormat, players: Vec<PlayerProfile>, palette: ColorPalette) -> Self {
+        let participant_count = players.len();
+        let fixtures = match format {
+            MatchFormat::Open => Vec::new(),
+            MatchFormat::Series { games } => vec![(0..participant_count).collect(); games],
+            MatchFormat::League => (0..participant_count)
+                .flat_map(|first| (first + 1..participant_count).map(move |second| vec![first, second]))
+                .collect(),
+        };
+        Self { format, players, palette, fixtures, games: Vec::new() }
+    }
+
+    pub fn game_count(&self) -> Option<usize> {
+        match self.format {
+            MatchFormat::Open => None,
+            _ => Some(self.fixtures.len())
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.game_count().is_some_and(|count| self.games.len() >= count)
+    }
+
+    // participants of the game being played or about to be played
+    pub fn current_participants(&self) -> Vec<usize> {
+        match self.format {
+            MatchFormat::Open => (0..self.players.len()).collect(),
+            _ => self.fixtures.get(self.games.len()).cloned().unwrap_or_default()
+        }
+    }
+
+    // first game of the match
+    pub fn first_game(&self) -> Game {
+        Game::with_players(self.current_participants().iter().map(|&participant| self.players[participant].clone()).collect())
+    }
+
+    // next game, the first player rotates when the same participants play again
+    pub fn next_game(&self, previous: &Game) -> Option<Game> {
+        match self.format {
+            _ if self.is_finished() => None,
+            MatchFormat::League => Some(self.first_game().with_options_of(previous)),
+            MatchFormat::Open | MatchFormat::Series { .. } => Some(previous.rematch()),
+        }
+    }
+
+    // a player scores a point for each player ranked lower and half a point for each tie
+    pub fn record(&mut self, results: &GameResults) {
+        let participants = self.current_participants();
+        let mut remaining_blocks = vec![0; participants.len()];
+        let mut points = vec![0.0; participants.len()];
+        for result in &results.players {
+            remaining_blocks[result.player] = result.remaining_blocks;
+            points[result.player] = results.players.iter()
+                .filter(|other| other.player != result.player)
+                .map(|other| match other.rank.cmp(&result.rank) {
+                    std::cmp::Ordering::Greater => 1.0,
+                    std::cmp::Ordering::Equal => 0.5,
+                    std::cmp::Ordering::Less => 0.0,
+                })
+                .sum();
+        }
+        self.games.push(GameRecord { participants, remaining_blocks, points });
+
+        if self.is_finished() {
+            self.save_ranking();
+        }
+    }
+
+    // ordered by points, then by fewer blocks left over all games
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = (0..self.players.len())
+            .map(|participant| Standing { participant, games: 0, wins: 0, points: 0.0, remaining_blocks: 0 })
+            .collect();
+
+        for game in &self.games {
+            let best_points = game.points.iter().copied().fold(f32::MIN, f32::max);
+            for (seat, &participant) in game.participants.iter().enumerate() {
+                let standing = &mut standings[participant];
+                standing.games += 1;
+                standing.points += game.points[seat];
+                standing.remaining_blocks += game.remaining_blocks[seat];
+                // a shared first place is not a win
+                if game.points[seat] == best_points && game.points.iter().filter(|&&points| points == best_points).count() == 1 {
+                    standing.wins += 1;
+                }
+            }
+        }
+
+        standings.sort_by(|a, b| b.points.total_cmp(&a.points).then(a.remaining_blocks.cmp(&b.remaining_blocks)));
+        standings
+    }
+
+    fn save_ranking(&self) {
+        let mut ranking = format!("{:?} match of {} games finished at {}\n", self.format, self.games.len(), timestamp());
+        for (place, standing) in self.standings().iter().enumerate() {
+            ranking += &format!(
+                "{}. {} ({}) - {} points, {} wins, {} blocks left in {} games\n",
+                place + 1,
+                self.players[standing.participant].name,
+                self.players[standing.participant].color_name(self.palette),
+                standing.points,
+                standing.wins,
+                standing.remaining_blocks,
+                standing.games
+            );
+        }
+        ranking += "\n";
+        append_to_config_file(RESULTS_FILE, &ranking, "final ranking");
+    }
+}
+
+// names, colors and ranks of the players of a finished game
+pub fn save_game_record(game: &Game, results: &GameResults) {
+    let mut record = format!("Game of {} players finished at {}, decided by {:?}\n", game.player_count, timestamp(), results.decided_by);
+    for result in &results.players {
+        record += &format!(
+            "{}. {} ({}) - {} blocks left, largest piece {}, {} pieces placed\n",
+            result.rank,
+            game.player_name(result.player),
+            game.palette.color_name(game.palette_index(result.player)),
+            result.remaining_blocks,
+            result.largest_piece,
+            result.pieces_placed
+        );
+    }
+    record += "\n";
+    append_to_config_file(GAMES_FILE, &record, "game record");
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+// `what` names the text in the messages
+fn append_to_config_file(file_name: &str, text: &str, what: &str) {
+    let Some(dir) = config_dir() else {
+        println!("No config directory to save the {what} to");
+        return;
+    };
+    let path = dir.join(file_name);
+    let saved = fs::create_dir_all(&dir)
+        .and_then(|()| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| file.write_all(text.as_bytes()));
+    match saved {
+        Ok(()) => println!("The {what} is saved to {}", path.display()),
+        Err(error) => println!("Failed to save the {what} to {}: {error}", path.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn players(count: usize) -> Vec<PlayerProfile> {
+        (0..count).map(|index| PlayerProfile { name: format!("P{index}"), color: index }).collect()
+    }
+
+    #[test]
+    fn league_pairs_every_two_participants_once() {
+        let league = Match::new(MatchFormat::League, players(4), ColorPalette::Standard);
+        assert_eq!(league.game_count(), Some(6));
+        let mut pairs: Vec<Vec<usize>> = league.fixtures.clone();
+        pairs.sort();
+        assert_eq!(pairs, [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]]);
+        assert_eq!(league.current_participants(), [0, 1]);
+    }
+
+    #[test]
+    fn series_and_open_matches_play_everyone() {
+        let series = Match::new(MatchFormat::Series { games: 3 }, players(3), ColorPalette::Standard);
+        assert_eq!(series.game_count(), Some(3));
+        assert!(series.fixtures.iter().all(|fixture| *fixture == [0, 1, 2]));
+
+        let open = Match::new(MatchFormat::Open, players(2), ColorPalette::Standard);
+        assert_eq!(open.game_count(), None);
+        assert!(!open.is_finished());
+        assert_eq!(open.current_participants(), [0, 1]);
+    }
+
+    #[test]
+    fn standings_rank_by_points_then_blocks_left() {
+        let mut league = Match::new(MatchFormat::League, players(3), ColorPalette::Standard);
+        league.games = vec![
+            GameRecord { participants: vec![0, 1], remaining_blocks: vec![0, 5], points: vec![1.0, 0.0] },
+            GameRecord { participants: vec![0, 2], remaining_blocks: vec![4, 4], points: vec![0.5, 0.5] },
+            GameRecord { participants: vec![1, 2], remaining_blocks: vec![3, 6], points: vec![1.0, 0.0] },
+        ];
+        assert!(league.is_finished());
+
+        let standings = league.standings();
+        let order: Vec<usize> = standings.iter().map(|standing| standing.participant).collect();
+        assert_eq!(order, [0, 1, 2]);
+        assert_eq!(standings[0].points, 1.5);
+        assert_eq!(standings[0].wins, 1);
+        assert_eq!(standings[0].remaining_blocks, 4);
+        // a draw is not a win for either player
+        assert_eq!(standings[2].wins, 0);
+        assert!(standings.iter().all(|standing| standing.games == 2));
+    }
+
+    #[test]
+    fn equal_points_go_to_fewer_blocks_left() {
+        let mut series = Match::new(MatchFormat::Series { games: 2 }, players(2), ColorPalette::Standard);
+        series.games = vec![
+            GameRecord { participants: vec![0, 1], remaining_blocks: vec![2, 0], points: vec![1.0, 0.0] },
+            GameRecord { participants: vec![0, 1], remaining_blocks: vec![9, 1], points: vec![0.0, 1.0] },
+        ];
+        let order: Vec<usize> = series.standings().iter().map(|standing| standing.participant).collect();
+        assert_eq!(order, [1, 0]);
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use crate::hex::Hex;
+use crate::game::*;
+use crate::locale::Locale;
+use crate::settings::Settings;
+
+// one message per line, words separated by spaces, hexes written as q,r
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetMessage {
+    // server -> client
+    // the token lets the player take the seat back after losing the connection,
+    // followed by the `moves` moves of the game so far and the absent players
+    Welcome { seat: usize, player_count: usize, token: u64, moves: usize },
+    Spectating { player_count: usize },
+    Placed { player: usize, shape: usize, hexes: Vec<Hex> },
+    Passed { player: usize },
+    Absent { player: usize },
+    Returned { player: usize },
+    Rejected,
+    Full,
+    Expired,
+    // client -> server, the first message is join, rejoin or spectate
+    Join,
+    Rejoin { token: u64 },
+    Spectate,
+    Place { shape: usize, hexes: Vec<Hex> },
+    Pass,
+}
+
+impl NetMessage {
+    pub fn to_line(&self) -> String {
+        let hexes_to_string = |hexes: &[Hex]| hexes
+            .iter()
+            .map(|hex| format!("{},{}", hex.q, hex.r))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        match self {
+            NetMessage::Welcome { seat, player_count, token, moves } => format!("welcome {seat} {player_count} {token} {moves}\n"),
+            NetMessage::Spectating { player_count } => format!("spectating {player_count}\n"),
+            NetMessage::Placed { player, shape, hexes } => format!("placed {player} {shape} {}\n", hexes_to_string(hexes)),
+            NetMessage::Passed { player } => format!("passed {player}\n"),
+            NetMessage::Absent { player } => format!("absent {player}\n"),
+            NetMessage::Returned { player } => format!("returned {player}\n"),
+            NetMessage::Rejected => "rejected\n".to_string(),
+            NetMessage::Full => "full\n".to_string(),
+            NetMessage::Expired => "expired\n".to_string(),
+            NetMessage::Join => "join\n".to_string(),
+            NetMessage::Rejoin { token } => format!("rejoin {token}\n"),
+            NetMessage::Spectate => "spectate\n".to_string(),
+            NetMessage::Place { shape, hexes } => format!("place {shape} {}\n", hexes_to_string(hexes)),
+            NetMessage::Pass => "pass\n".to_string(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let command = words.next()?;
+        let mut number = || words.next()?.parse::<usize>().ok();
+
+        let message = match command {
+            "welcome" => {
+                let seat = number()?;
+                let player_count = number()?;
+                let token = words.next()?.parse().ok()?;
+                NetMessage::Welcome { seat, player_count, token, moves: words.next()?.parse().ok()? }
+            }
+            "spectating" => NetMessage::Spectating { player_count: number()? },
+            "placed" => {
+                let player = number()?;
+                let shape = number()?;
+                NetMessage::Placed { player, shape, hexes: parse_hexes(words)? }
+            }
+            "passed" => NetMessage::Passed { player: number()? },
+            "absent" => NetMessage::Absent { player: number()? },
+            "returned" => NetMessage::Returned { player: number()? },
+            "rejected" => NetMessage::Rejected,
+            "full" => NetMessage::Full,
+            "expired" => NetMessage::Expired,
+            "join" => NetMessage::Join,
+            "rejoin" => NetMessage::Rejoin { token: words.next()?.parse().ok()? },
+            "spectate" => NetMessage::Spectate,
+            "place" => {
+                let shape = number()?;
+                NetMessage::Place { shape, hexes: parse_hexes(words)? }
+            }
+            "pass" => NetMessage::Pass,
+            _ => return None
+        };
+        Some(message)
+    }
+}
+
+fn parse_hexes<'a>(words: impl Iterator<Item = &'a str>) -> Option<Vec<Hex>> {
+    words.map(|word| {
+        let (q, r) = word.split_once(',')?;
+        Some(Hex { q: q.parse().ok()?, r: r.parse().ok()? })
+    }).collect()
+}
+
+// reads lines from the stream until it is closed, calling `on_message` for each known message
+pub fn read_messages(stream: TcpStream, mut on_message: impl FnMut(NetMessage)) {
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if let Some(message) = NetMessage::parse(&line) {
+            on_message(message);
+        }
+    }
+}
+
+pub fn send_message(mut stream: &TcpStream, message: &NetMessage) {
+    if let Err(error) = stream.write_all(message.to_line().as_bytes()) {
+        warn!("Failed to send message: {error}");
+    }
+}
+
+// address given with --connect or --spectate or picked from the LAN games list
+#[derive(Resource)]
+pub struct ServerAddress {
+    pub address: String,
+    pub spectate: bool,
+}
+
+// time between attempts to get back to the server after losing the connection
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Resource)]
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Arc<Mutex<VecDeque<NetMessage>>>,
+    disconnected: Arc<AtomicBool>,
+}
+
+fn open_connection(address: &str, first_message: &NetMessage) -> std::io::Result<Connection> {
+    let stream = TcpStream::connect(address)?;
+    send_message(&stream, first_message);
+
+    let incoming = Arc::new(Mutex::new(VecDeque::new()));
+    let disconnected = Arc::new(AtomicBool::new(false));
+    let reader_stream = stream.try_clone()?;
+    let reader_incoming = incoming.clone();
+    let reader_disconnected = disconnected.clone();
+    thread::spawn(move || {
+        read_messages(reader_stream, |message| reader_incoming.lock().unwrap().push_back(message));
+        warn!("Disconnected from the server");
+        reader_disconnected.store(true, Ordering::Relaxed);
+    });
+
+    Ok(Connection { stream, incoming, disconnected })
+}
+
+// connects whenever a new address is set, dropping the previous connection
+pub fn connect_to_server(
+    address: Option<Res<ServerAddress>>,
+    old_connection: Option<Res<Connection>>,
+    mut commands: Commands,
+) {
+    let Some(address) = address.filter(|address| address.is_changed()) else {
+        return;
+    };
+    if let Some(old_connection) = old_connection {
+        let _ = old_connection.stream.shutdown(Shutdown::Both);
+        commands.remove_resource::<Connection>();
+    }
+
+    let first_message = if address.spectate { NetMessage::Spectate } else { NetMessage::Join };
+    match open_connection(&address.address, &first_message) {
+        Ok(connection) => {
+            info!("Connected to {}", address.address);
+            commands.insert_resource(connection);
+        }
+        Err(error) => warn!("Failed to connect to {}: {error}", address.address)
+    }
+}
+
+// a seated player who lost the connection keeps trying to take the seat back
+pub fn reconnect_to_server(
+    time: Res<Time>,
+    address: Option<Res<ServerAddress>>,
+    connection: Option<Res<Connection>>,
+    game: Res<Game>,
+    mut since_last_attempt: Local<Option<Duration>>,
+    mut commands: Commands,
+) {
+    let lost_connection = connection.is_some_and(|connection| connection.disconnected.load(Ordering::Relaxed));
+    let (Some(address), Some(token), true) = (address, game.session_token, lost_connection) else {
+        *since_last_attempt = None;
+        return;
+    };
+
+    let elapsed = since_last_attempt.map_or(RECONNECT_INTERVAL, |elapsed| elapsed + time.delta());
+    if elapsed < RECONNECT_INTERVAL {
+        *since_last_attempt = Some(elapsed);
+        return;
+    }
+    *since_last_attempt = Some(Duration::ZERO);
+
+    match open_connection(&address.address, &NetMessage::Rejoin { token }) {
+        Ok(connection) => {
+            info!("Reconnected to {}", address.address);
+            commands.insert_resource(connection);
+        }
+        Err(error) => warn!("Failed to reconnect to {}: {error}", address.address)
+    }
+}
+
+// moves are applied in the order the server sent them,
+// stopping after a pass so it is handled before the next move
+#[allow(clippy::too_many_arguments)]
+pub fn receive_messages(
+    connection: Option<Res<Connection>>,
+    state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut game: Option<ResMut<Game>>,
+    pieces: Query<(Entity, &PlayerIndex, &PieceShape, &Transform, &Children)>,
+    pending: Query<(Entity, &Transform), With<PendingPlacement>>,
+    mut ev_turn_passed: EventWriter<TurnPassedEvent>,
+    mut ev_sound: EventWriter<SoundEvent>,
+    address: Option<Res<ServerAddress>>,
+    mut discovery: Option<ResMut<LanDiscovery>>,
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    mut commands: Commands,
+) {
+    let Some(connection) = connection else {
+        return;
+    };
+    let mut incoming = connection.incoming.lock().unwrap();
+
+    while let Some(message) = incoming.front().cloned() {
+        let in_game = *state.get() == GameState::Game;
+        match (message, game.as_deref_mut()) {
+            (NetMessage::Welcome { seat, token, moves, .. }, Some(game)) if in_game && game.seat == Some(seat) => {
+                // back after a lost connection, moves seen before are in the history again
+                // and the absent players are sent over again
+                info!("Back in the game as player {}", seat + 1);
+                game.session_token = Some(token);
+                game.awaiting_server = false;
+                game.skip_moves = game.received_moves.min(moves);
+                game.received_moves -= game.skip_moves;
+                game.absent_players.clear();
+            }
+            (NetMessage::Welcome { seat, player_count, token, .. }, _) => {
+                info!("Joined as player {}", seat + 1);
+                let game = Game { seat: Some(seat), session_token: Some(token), ..Game::new(player_count, &locale) };
+                commands.insert_resource(settings.with_rule_defaults(game));
+                game_state.set(GameState::Game);
+                incoming.pop_front();
+                // the board is set up when the game state is entered
+                return;
+            }
+            (NetMessage::Spectating { player_count }, _) => {
+                info!("Watching the game");
+                commands.insert_resource(settings.with_rule_defaults(Game { spectator: true, ..Game::new(player_count, &locale) }));
+                game_state.set(GameState::Game);
+                incoming.pop_front();
+                return;
+            }
+            (NetMessage::Placed { .. } | NetMessage::Passed { .. }, Some(game)) if in_game && game.skip_moves > 0 => {
+                game.skip_moves -= 1;
+                game.received_moves += 1;
+            }
+            (NetMessage::Placed { player, shape, hexes }, Some(game)) if in_game => {
+                let piece = pieces.iter().find(|(_, &PlayerIndex(player_index), &PieceShape(piece_shape), _, _)|
+                    player_index == player && piece_shape == shape
+                );
+                if piece.is_none() {
+                    warn!("Piece {shape} of player {} is not in its tray, placing it on the board only", player + 1);
+                }
+                // the board follows the server even when the piece is missing here
+                game.current_player = player;
+                let piece = piece.map(|(piece_entity, _, _, piece_transform, blocks)| (piece_entity, piece_transform, blocks));
+                apply_placement(&mut commands, &mut ev_sound, game, piece, shape, &hexes);
+                game.awaiting_server = false;
+                game.received_moves += 1;
+            }
+            (NetMessage::Passed { player }, Some(game)) if in_game => {
+                game.received_moves += 1;
+                game.current_player = player;
+                game.awaiting_server = false;
+                ev_turn_passed.send(TurnPassedEvent);
+                incoming.pop_front();
+                return;
+            }
+            (NetMessage::Rejected, Some(game)) => {
+                warn!("Move rejected by the server");
+                game.awaiting_server = false;
+                for (piece_entity, piece_transform) in &pending {
+                    commands.entity(piece_entity)
+                        .remove::<PendingPlacement>()
+                        .insert(Tween::travel(*piece_transform, game.original_transform));
+                }
+            }
+            (NetMessage::Absent { player }, Some(game)) if in_game => {
+                if !game.absent_players.contains(&player) {
+                    game.absent_players.push(player);
+                }
+            }
+            (NetMessage::Returned { player }, Some(game)) if in_game => game.absent_players.retain(|&absent| absent != player),
+            (NetMessage::Expired, Some(game)) => {
+                warn!("The seat was given away while disconnected");
+                game.session_token = None;
+                game_state.set(GameState::Menu);
+            }
+            (NetMessage::Full, _) => {
+                warn!("Server is full");
+                // shown in the list of LAN games the join came from
+                if let Some(discovery) = discovery.as_deref_mut() {
+                    discovery.full_game = address.as_ref().and_then(|address| address.address.parse().ok());
+                }
+            }
+            (NetMessage::Placed { .. } | NetMessage::Passed { .. } | NetMessage::Absent { .. } | NetMessage::Returned { .. }, _) => return,
+            (NetMessage::Rejected | NetMessage::Expired | NetMessage::Join | NetMessage::Rejoin { .. } | NetMessage::Spectate |
+                NetMessage::Place { .. } | NetMessage::Pass, _) => ()
+        }
+        incoming.pop_front();
+    }
+}
+
+// forwards moves of the local player to the server
+pub fn send_requests(
+    connection: Option<Res<Connection>>,
+    mut game: ResMut<Game>,
+    mut ev_pass: EventReader<PassTurnEvent>,
+    mut ev_placement: EventReader<PlacementRequestEvent>,
+) {
+    let Some(connection) = connection.filter(|_| game.seat.is_some()) else {
+        ev_pass.clear();
+        ev_placement.clear();
+        return;
+    };
+
+    for ev in ev_placement.read() {
+        if game.is_local_turn() {
+            send_message(&connection.stream, &NetMessage::Place { shape: ev.shape, hexes: ev.hexes.clone() });
+            game.awaiting_server = true;
+        }
+    }
+
+    for _ev in ev_pass.read() {
+        if game.is_local_turn() {
+            send_message(&connection.stream, &NetMessage::Pass);
+            game.awaiting_server = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        let hexes = vec![Hex { q: 0, r: 0 }, Hex { q: -3, r: 2 }, Hex { q: 12, r: -7 }];
+        let messages = [
+            NetMessage::Welcome { seat: 2, player_count: 4, token: u64::MAX, moves: 17 },
+            NetMessage::Spectating { player_count: 3 },
+            NetMessage::Placed { player: 1, shape: 20, hexes: hexes.clone() },
+            NetMessage::Passed { player: 3 },
+            NetMessage::Absent { player: 0 },
+            NetMessage::Returned { player: 5 },
+            NetMessage::Rejected,
+            NetMessage::Full,
+            NetMessage::Expired,
+            NetMessage::Join,
+            NetMessage::Rejoin { token: 42 },
+            NetMessage::Spectate,
+            NetMessage::Place { shape: 0, hexes },
+            NetMessage::Pass,
+        ];
+        for message in messages {
+            let line = message.to_line();
+            assert!(line.ends_with('\n'));
+            assert_eq!(NetMessage::parse(&line), Some(message));
+        }
+    }
+
+    #[test]
+    fn broken_lines_are_not_messages() {
+        for line in ["", "hello", "welcome 1 4", "placed 1 2 3", "placed 1 2 0,x", "passed -1", "rejoin token"] {
+            assert_eq!(NetMessage::parse(line), None, "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use crate::hex::Hex;
+use crate::game::*;
+use crate::settings::Settings;
+
+pub const PALETTE_SIZE: usize = 8;
+// same size as hex.png so the pattern scales with the block
+const PATTERN_TEXTURE_SIZE: u32 = 128;
+const PATTERN_DARK: Color = Color::rgba(0.0, 0.0, 0.0, 0.55);
+const PATTERN_LIGHT: Color = Color::rgba(1.0, 1.0, 1.0, 0.55);
+// relative luminance of the block color under which the pattern is drawn light
+const DARK_BLOCK_LUMINANCE: f32 = 0.18;
+const PATTERN_Z: f32 = 0.1;
+
+// colors players pick from, each palette keeps its colors apart for one kind of vision
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ColorPalette {
+    #[default]
+    Standard,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+impl ColorPalette {
+    pub const ALL: [ColorPalette; 5] = [
+        ColorPalette::Standard,
+        ColorPalette::Deuteranopia,
+        ColorPalette::Protanopia,
+        ColorPalette::Tritanopia,
+        ColorPalette::HighContrast,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorPalette::Standard => "Standard",
+            ColorPalette::Deuteranopia => "Deuteranopia",
+            ColorPalette::Protanopia => "Protanopia",
+            ColorPalette::Tritanopia => "Tritanopia",
+            ColorPalette::HighContrast => "High contrast",
+        }
+    }
+
+    // key of the name shown in the menu in the locale files, `name` is the one in the settings file
+    pub fn locale_key(self) -> &'static str {
+        match self {
+            ColorPalette::Standard => "palette.standard",
+            ColorPalette::Deuteranopia => "palette.deuteranopia",
+            ColorPalette::Protanopia => "palette.protanopia",
+            ColorPalette::Tritanopia => "palette.tritanopia",
+            ColorPalette::HighContrast => "palette.high_contrast",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&palette| palette == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // the first six colors are the defaults of the seats
+    pub fn colors(self) -> [(&'static str, Color); PALETTE_SIZE] {
+        match self {
+            // hues spread evenly around the color wheel
+            ColorPalette::Standard => [
+                ("red", Color::hsl(0.0, 1.0, 0.5)),
+                ("yellow", Color::hsl(60.0, 1.0, 0.5)),
+                ("green", Color::hsl(120.0, 1.0, 0.5)),
+                ("cyan", Color::hsl(180.0, 1.0, 0.5)),
+                ("blue", Color::hsl(240.0, 1.0, 0.5)),
+                ("magenta", Color::hsl(300.0, 1.0, 0.5)),
+                ("orange", Color::hsl(30.0, 1.0, 0.5)),
+                ("violet", Color::hsl(270.0, 1.0, 0.5)),
+            ],
+            // Okabe-Ito colors, safe without green cones
+            ColorPalette::Deuteranopia => [
+                ("orange", Color::hex("E69F00").unwrap()),
+                ("sky blue", Color::hex("56B4E9").unwrap()),
+                ("bluish green", Color::hex("009E73").unwrap()),
+                ("yellow", Color::hex("F0E442").unwrap()),
+                ("blue", Color::hex("0072B2").unwrap()),
+                ("vermillion", Color::hex("D55E00").unwrap()),
+                ("reddish purple", Color::hex("CC79A7").unwrap()),
+                ("grey", Color::hex("999999").unwrap()),
+            ],
+            // same colors with the reds, which look dark without red cones, last
+            ColorPalette::Protanopia => [
+                ("blue", Color::hex("0072B2").unwrap()),
+                ("orange", Color::hex("E69F00").unwrap()),
+                ("sky blue", Color::hex("56B4E9").unwrap()),
+                ("yellow", Color::hex("F0E442").unwrap()),
+                ("bluish green", Color::hex("009E73").unwrap()),
+                ("grey", Color::hex("999999").unwrap()),
+                ("reddish purple", Color::hex("CC79A7").unwrap()),
+                ("vermillion", Color::hex("D55E00").unwrap()),
+            ],
+            // blue against yellow and green against blue are avoided
+            ColorPalette::Tritanopia => [
+                ("red", Color::hex("CC3311").unwrap()),
+                ("teal", Color::hex("009988").unwrap()),
+                ("magenta", Color::hex("EE3377").unwrap()),
+                ("black", Color::hex("222222").unwrap()),
+                ("pink", Color::hex("FFAABB").unwrap()),
+                ("grey", Color::hex("999999").unwrap()),
+                ("wine", Color::hex("882255").unwrap()),
+                ("cyan", Color::hex("33BBEE").unwrap()),
+            ],
+            // saturated colors with very different brightness
+            ColorPalette::HighContrast => [
+                ("black", Color::hex("000000").unwrap()),
+                ("yellow", Color::hex("FFE000").unwrap()),
+                ("blue", Color::hex("0033CC").unwrap()),
+                ("red", Color::hex("E00000").unwrap()),
+                ("cyan", Color::hex("00E5FF").unwrap()),
+                ("magenta", Color::hex("E000E0").unwrap()),
+                ("green", Color::hex("00A000").unwrap()),
+                ("orange", Color::hex("FF8000").unwrap()),
+            ],
+        }
+    }
+
+    pub fn color(self, index: usize) -> Color {
+        self.colors()[index].1
+    }
+
+    pub fn color_name(self, index: usize) -> &'static str {
+        self.colors()[index].0
+    }
+}
+
+// variants keep the hue, lighter ones move toward white
+pub fn shade(color: Color, saturation_factor: f32, lightness: impl Fn(f32) -> f32) -> Color {
+    match color.as_hsla() {
+        Color::Hsla { hue, saturation, lightness: current, alpha } =>
+            Color::hsla(hue, saturation * saturation_factor, lightness(current), alpha),
+        other => other
+    }
+}
+
+#[derive(Resource)]
+pub struct PlayerPatterns(Vec<Handle<Image>>);
+
+impl PlayerPatterns {
+    pub fn get(&self, player: usize) -> Handle<Image> {
+        self.0[player % self.0.len()].clone()
+    }
+}
+
+// pattern over a board hex, shown when a player covers it
+#[derive(Component)]
+pub struct BoardPattern;
+
+pub fn create_player_patterns(mut images: ResMut<Assets<Image>>, mut commands: Commands) {
+    let half = PATTERN_TEXTURE_SIZE as f32 / 2.0;
+    let patterns = (0..MAX_PLAYERS).map(|player| {
+        let mut data = Vec::with_capacity((PATTERN_TEXTURE_SIZE * PATTERN_TEXTURE_SIZE * 4) as usize);
+        for y in 0..PATTERN_TEXTURE_SIZE {
+            for x in 0..PATTERN_TEXTURE_SIZE {
+                let point = Vec2 { x: x as f32 + 0.5 - half, y: y as f32 + 0.5 - half };
+                let alpha = if is_pattern_pixel(player, point) { 255 } else { 0 };
+                data.extend_from_slice(&[255, 255, 255, alpha]);
+            }
+        }
+        let image = Image::new(
+            Extent3d { width: PATTERN_TEXTURE_SIZE, height: PATTERN_TEXTURE_SIZE, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        images.add(image)
+    }).collect();
+
+    commands.insert_resource(PlayerPatterns(patterns));
+}
+
+// `point` is relative to the center of the hex, y pointing down
+fn is_pattern_pixel(player: usize, point: Vec2) -> bool {
+    let distance = point.length();
+    match player {
+        // dot
+        0 => distance < 22.0,
+        // horizontal stripes
+        1 => distance < 46.0 && (point.y + 64.0).rem_euclid(24.0) < 9.0,
+        // plus
+        2 => (point.x.abs() < 8.0 && point.y.abs() < 34.0) || (point.y.abs() < 8.0 && point.x.abs() < 34.0),
+        // ring
+        3 => distance > 24.0 && distance < 34.0,
+        // diagonal stripes
+        4 => distance < 46.0 && (point.x + point.y + 256.0).rem_euclid(28.0) < 10.0,
+        // triangle pointing up
+        _ => point.y > -32.0 && point.y < 24.0 && point.x.abs() < (point.y + 32.0) * 32.0 / 56.0,
+    }
+}
+
+// light on dark blocks and dark on light ones
+fn pattern_color(block_color: Color) -> Color {
+    let [red, green, blue, _] = block_color.as_linear_rgba_f32();
+    let luminance = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+    if luminance < DARK_BLOCK_LUMINANCE { PATTERN_LIGHT } else { PATTERN_DARK }
+}
+
+pub fn pattern_sprite(patterns: &PlayerPatterns, player: usize, block_color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color: pattern_color(block_color),
+            ..default()
+        },
+        texture: patterns.get(player),
+        transform: Transform::from_xyz(0.0, 0.0, PATTERN_Z),
+        ..default()
+    }
+}
+
+// copied into every game when it starts
+pub fn apply_display_settings(settings: Res<Settings>, mut game: ResMut<Game>) {
+    game.palette = settings.palette;
+    game.show_patterns = settings.patterns;
+}
+
+pub fn board_pattern_system(
+    game: Res<Game>,
+    patterns: Res<PlayerPatterns>,
+    landing: Query<&Landing>,
+    mut landed: RemovedComponents<Landing>,
+    mut board_patterns: Query<(&Hex, &mut Handle<Image>, &mut Sprite, &mut Visibility), With<BoardPattern>>,
+) {
+    let landed = landed.read().count() > 0;
+    if !game.is_changed() && landing.is_empty() && !landed {
+        return;
+    }
+
+    // cells under pieces still on their way keep what they showed before
+    let landing_cells = landing_cells(&landing);
+    for (hex, mut texture, mut sprite, mut visibility) in &mut board_patterns {
+        match landing_cells.get(hex).or_else(|| game.board.get(hex)) {
+            Some(Cell::Player(player)) if game.show_patterns => {
+                *texture = patterns.get(*player);
+                sprite.color = pattern_color(game.player_color(*player));
+                *visibility = Visibility::Inherited;
+            }
+            _ => *visibility = Visibility::Hidden
+        }
+    }
+}
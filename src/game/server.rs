@@ -0,0 +1,495 @@
+use std::collections::HashSet;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::hex::Hex;
+use crate::game::*;
+use crate::game::discovery::spawn_discovery_responder;
+use crate::game::hint::legal_placements;
+use crate::game::network::{read_messages, send_message, NetMessage};
+use crate::game::setup::{build_board, piece_shape, ALL_PIECES};
+
+pub const SUPPORTED_PLAYER_COUNTS: [usize; 4] = [2, 3, 4, 6];
+
+// how often held seats are checked when no client sends anything
+const SERVER_TICK: Duration = Duration::from_millis(250);
+
+type ClientId = usize;
+type SessionToken = u64;
+
+// what the server does on the turn of a player who lost the connection
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AbsentSeatPolicy {
+    // wait for the player to come back
+    Pause,
+    AutoPass,
+    // place the largest piece that fits
+    Ai,
+}
+
+impl AbsentSeatPolicy {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pause" => Some(AbsentSeatPolicy::Pause),
+            "pass" => Some(AbsentSeatPolicy::AutoPass),
+            "ai" => Some(AbsentSeatPolicy::Ai),
+            _ => None
+        }
+    }
+}
+
+pub struct LobbySettings {
+    pub player_count: usize,
+    // time a disconnected player has to reconnect before the seat is given away
+    pub seat_hold: Duration,
+    pub absent_seat_policy: AbsentSeatPolicy,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Seat {
+    Open,
+    Taken { token: SessionToken },
+    // the player disconnected and can come back with the token until the hold time ends
+    Held { token: SessionToken, since: Instant },
+    // hold time is over, anyone can take the seat, until then it is played by the policy
+    Abandoned,
+}
+
+impl Seat {
+    fn is_free(&self) -> bool {
+        matches!(self, Seat::Open | Seat::Abandoned)
+    }
+
+    fn is_absent(&self) -> bool {
+        matches!(self, Seat::Held { .. } | Seat::Abandoned)
+    }
+}
+
+enum ServerEvent {
+    Connected(ClientId, TcpStream),
+    Message(ClientId, NetMessage),
+    Disconnected(ClientId),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    // connected, has not asked to join or spectate yet
+    Pending,
+    Player(usize),
+    Spectator,
+}
+
+struct Client {
+    id: ClientId,
+    role: Role,
+    stream: TcpStream,
+}
+
+// authoritative state of the game, same rules as the client
+struct ServerGame {
+    board: Board,
+    player_count: usize,
+    current_player: usize,
+    pass_turn_count: usize,
+    // shapes each player has not placed yet
+    remaining_shapes: Vec<Vec<usize>>,
+    // every accepted move, replayed to clients joining later
+    history: Vec<NetMessage>,
+}
+
+impl ServerGame {
+    fn new(player_count: usize) -> Self {
+        Self {
+            board: build_board(player_count),
+            player_count,
+            current_player: 0,
+            pass_turn_count: 0,
+            remaining_shapes: vec![(0..ALL_PIECES.len()).collect(); player_count],
+            history: Vec::new(),
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        self.pass_turn_count >= self.player_count
+    }
+
+    fn place(&mut self, player: usize, shape: usize, hexes: Vec<Hex>) -> Option<NetMessage> {
+        if self.is_over() || player != self.current_player || !self.remaining_shapes[player].contains(&shape) {
+            return None;
+        }
+
+        let piece_fits = is_same_shape(&piece_shape(shape), &hexes) &&
+            hexes.iter().all(|hex| is_hex_free(&self.board, hex)) &&
+            piece_can_be_placed_on_board(&self.board, &hexes, player);
+        if !piece_fits {
+            return None;
+        }
+
+        for &hex in &hexes {
+            self.board.insert(hex, Cell::Player(player));
+        }
+        self.remaining_shapes[player].retain(|&remaining| remaining != shape);
+        self.pass_turn_count = 0;
+        self.current_player = (player + 1) % self.player_count;
+
+        let message = NetMessage::Placed { player, shape, hexes };
+        self.history.push(message.clone());
+        Some(message)
+    }
+
+    // move for a player who is not connected, None when the game waits for them
+    fn play_absent(&mut self, player: usize, policy: AbsentSeatPolicy) -> Option<NetMessage> {
+        match policy {
+            AbsentSeatPolicy::Pause => None,
+            AbsentSeatPolicy::AutoPass => self.pass(player),
+            AbsentSeatPolicy::Ai => {
+                let mut shapes = self.remaining_shapes[player].clone();
+                shapes.sort_by_key(|&shape| std::cmp::Reverse(ALL_PIECES[shape].len()));
+                let placement = shapes.into_iter().find_map(|shape|
+                    legal_placements(&self.board, &piece_shape(shape), player)
+                        .into_iter()
+                        .next()
+                        .map(|hexes| (shape, hexes))
+                );
+                match placement {
+                    Some((shape, hexes)) => self.place(player, shape, hexes),
+                    None => self.pass(player)
+                }
+            }
+        }
+    }
+
+    fn pass(&mut self, player: usize) -> Option<NetMessage> {
+        if self.is_over() || player != self.current_player {
+            return None;
+        }
+
+        self.pass_turn_count += 1;
+        self.current_player = (player + 1) % self.player_count;
+
+        let message = NetMessage::Passed { player };
+        self.history.push(message.clone());
+        Some(message)
+    }
+}
+
+// blocks cover the hexes in some rotation, flipped or not
+fn is_same_shape(shape: &[Hex], hexes: &[Hex]) -> bool {
+    if shape.len() != hexes.len() || hexes.is_empty() {
+        return false;
+    }
+
+    let target: HashSet<Hex> = hexes.iter().copied().collect();
+    let flipped: Vec<Hex> = shape.iter().map(|block| block.reflect()).collect();
+
+    let same_shape = [shape, &flipped].into_iter().any(|blocks|
+        ALL_ROTATIONS.into_iter().any(|rotation| {
+            let rotated: Vec<Hex> = blocks.iter().map(|block| block.rotate(rotation)).collect();
+            rotated.iter().any(|&block| {
+                let offset = hexes[0] - block;
+                rotated.iter().map(|&hex| hex + offset).collect::<HashSet<Hex>>() == target
+            })
+        })
+    );
+    same_shape
+}
+
+fn broadcast(clients: &[Client], message: &NetMessage) {
+    for client in clients.iter().filter(|client| client.role != Role::Pending) {
+        send_message(&client.stream, message);
+    }
+}
+
+pub fn run_server(address: &str, settings: LobbySettings) -> std::io::Result<()> {
+    let player_count = settings.player_count;
+    if !SUPPORTED_PLAYER_COUNTS.contains(&player_count) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("unsupported player count {player_count}, expected one of {SUPPORTED_PLAYER_COUNTS:?}")
+        ));
+    }
+
+    let listener = TcpListener::bind(address)?;
+    eprintln!(
+        "Server for {player_count} players listening on {address}, seats are held for {}s, absent players {:?}",
+        settings.seat_hold.as_secs(), settings.absent_seat_policy
+    );
+
+    let free_seats = Arc::new(AtomicUsize::new(player_count));
+    spawn_discovery_responder(listener.local_addr()?, player_count, free_seats.clone());
+
+    let (sender, receiver) = mpsc::channel::<ServerEvent>();
+    let accept_sender = sender.clone();
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().flatten().enumerate() {
+            if accept_sender.send(ServerEvent::Connected(id, stream)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut game = ServerGame::new(player_count);
+    let mut clients: Vec<Client> = Vec::new();
+    let mut seats = vec![Seat::Open; player_count];
+
+    loop {
+        match receiver.recv_timeout(SERVER_TICK) {
+            Ok(ServerEvent::Connected(id, stream)) => {
+                // a connection that can not be read from is dropped, the others go on
+                let reader_stream = match stream.try_clone() {
+                    Ok(reader_stream) => reader_stream,
+                    Err(error) => {
+                        eprintln!("Dropped connection {id}: {error}");
+                        continue;
+                    }
+                };
+                let reader_sender = sender.clone();
+                thread::spawn(move || {
+                    read_messages(reader_stream, |message| {
+                        let _ = reader_sender.send(ServerEvent::Message(id, message));
+                    });
+                    let _ = reader_sender.send(ServerEvent::Disconnected(id));
+                });
+
+                clients.push(Client { id, role: Role::Pending, stream });
+            }
+            Ok(ServerEvent::Message(id, message)) => {
+                let Some(index) = clients.iter().position(|client| client.id == id) else {
+                    continue;
+                };
+
+                let accepted = match (clients[index].role, message) {
+                    (Role::Pending, NetMessage::Join) => {
+                        let Some(seat) = seats.iter().position(Seat::is_free) else {
+                            send_message(&clients[index].stream, &NetMessage::Full);
+                            continue;
+                        };
+                        let token = rand::random::<SessionToken>();
+                        seats[seat] = Seat::Taken { token };
+
+                        // the history brings a player joining late up to date
+                        let moves = game.history.len();
+                        send_message(&clients[index].stream, &NetMessage::Welcome { seat, player_count, token, moves });
+                        for message in &game.history {
+                            send_message(&clients[index].stream, message);
+                        }
+                        for (player, _) in seats.iter().enumerate().filter(|(_, seat)| seat.is_absent()) {
+                            send_message(&clients[index].stream, &NetMessage::Absent { player });
+                        }
+                        eprintln!("Player {} joined", seat + 1);
+                        clients[index].role = Role::Player(seat);
+                        broadcast(&clients, &NetMessage::Returned { player: seat });
+                        None
+                    }
+                    (Role::Pending, NetMessage::Rejoin { token }) => {
+                        let held_seat = seats.iter().position(|seat| matches!(seat, Seat::Held { token: held, .. } if *held == token));
+                        let Some(seat) = held_seat else {
+                            send_message(&clients[index].stream, &NetMessage::Expired);
+                            continue;
+                        };
+                        seats[seat] = Seat::Taken { token };
+
+                        let moves = game.history.len();
+                        send_message(&clients[index].stream, &NetMessage::Welcome { seat, player_count, token, moves });
+                        for message in &game.history {
+                            send_message(&clients[index].stream, message);
+                        }
+                        for (player, _) in seats.iter().enumerate().filter(|(_, seat)| seat.is_absent()) {
+                            send_message(&clients[index].stream, &NetMessage::Absent { player });
+                        }
+                        eprintln!("Player {} reconnected", seat + 1);
+                        clients[index].role = Role::Player(seat);
+                        broadcast(&clients, &NetMessage::Returned { player: seat });
+                        None
+                    }
+                    (Role::Pending, NetMessage::Spectate) => {
+                        send_message(&clients[index].stream, &NetMessage::Spectating { player_count });
+                        for message in &game.history {
+                            send_message(&clients[index].stream, message);
+                        }
+                        for (player, _) in seats.iter().enumerate().filter(|(_, seat)| seat.is_absent()) {
+                            send_message(&clients[index].stream, &NetMessage::Absent { player });
+                        }
+                        eprintln!("Spectator joined");
+                        clients[index].role = Role::Spectator;
+                        None
+                    }
+                    (Role::Player(seat), NetMessage::Place { shape, hexes }) => {
+                        let accepted = game.place(seat, shape, hexes);
+                        if accepted.is_none() {
+                            send_message(&clients[index].stream, &NetMessage::Rejected);
+                        }
+                        accepted
+                    }
+                    (Role::Player(seat), NetMessage::Pass) => {
+                        let accepted = game.pass(seat);
+                        if accepted.is_none() {
+                            send_message(&clients[index].stream, &NetMessage::Rejected);
+                        }
+                        accepted
+                    }
+                    _ => {
+                        send_message(&clients[index].stream, &NetMessage::Rejected);
+                        None
+                    }
+                };
+
+                if let Some(message) = accepted {
+                    broadcast(&clients, &message);
+                    if game.is_over() {
+                        eprintln!("Game over");
+                    }
+                }
+            }
+            Ok(ServerEvent::Disconnected(id)) => {
+                if let Some(index) = clients.iter().position(|client| client.id == id) {
+                    match clients[index].role {
+                        Role::Player(seat) => {
+                            eprintln!("Player {} lost the connection, seat is held", seat + 1);
+                            if let Seat::Taken { token } = seats[seat] {
+                                seats[seat] = Seat::Held { token, since: Instant::now() };
+                            }
+                            clients.remove(index);
+                            broadcast(&clients, &NetMessage::Absent { player: seat });
+                        }
+                        Role::Spectator => {
+                            eprintln!("Spectator left");
+                            clients.remove(index);
+                        }
+                        Role::Pending => {
+                            clients.remove(index);
+                        }
+                    }
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => (),
+            Err(mpsc::RecvTimeoutError::Disconnected) => break
+        }
+
+        for (player, seat) in seats.iter_mut().enumerate() {
+            if matches!(seat, Seat::Held { since, .. } if since.elapsed() >= settings.seat_hold) {
+                eprintln!("Player {} did not come back, the seat is open", player + 1);
+                *seat = Seat::Abandoned;
+            }
+        }
+        free_seats.store(seats.iter().filter(|seat| seat.is_free()).count(), Ordering::Relaxed);
+
+        // a seat nobody came back to is not waited for
+        while !game.is_over() && seats[game.current_player].is_absent() {
+            let player = game.current_player;
+            let policy = match seats[player] {
+                Seat::Abandoned if settings.absent_seat_policy == AbsentSeatPolicy::Pause => AbsentSeatPolicy::AutoPass,
+                _ => settings.absent_seat_policy
+            };
+            let Some(message) = game.play_absent(player, policy) else {
+                break;
+            };
+            broadcast(&clients, &message);
+            if game.is_over() {
+                eprintln!("Game over");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::setup::piece_shape;
+
+    const LINE_OF_FIVE: usize = 0;
+    const LINE_OF_FOUR: usize = 1;
+    const ZIGZAG: usize = 2;
+    const PAIR: usize = 9;
+    const SINGLE: usize = 10;
+
+    fn start_of(game: &ServerGame, player: usize) -> Hex {
+        *game.board.iter().find(|(_, cell)| **cell == Cell::PlayerStart(player)).unwrap().0
+    }
+
+    // the blocks turned, flipped when asked, and moved to cover the start of the player
+    fn placement_at_start(game: &ServerGame, player: usize, blocks: &[Hex], flipped: bool) -> Vec<Hex> {
+        let start = start_of(game, player);
+        let blocks: Vec<Hex> = blocks.iter().map(|&block| if flipped { block.reflect() } else { block }).collect();
+        ALL_ROTATIONS.into_iter().flat_map(|rotation| {
+            let rotated: Vec<Hex> = blocks.iter().map(|block| block.rotate(rotation)).collect();
+            rotated.clone().into_iter().map(move |anchor| rotated.iter().map(|&block| block - anchor + start).collect::<Vec<Hex>>())
+        }).find(|hexes| hexes.iter().all(|hex| is_hex_free(&game.board, hex))).unwrap()
+    }
+
+    #[test]
+    fn moves_out_of_turn_are_rejected() {
+        let mut game = ServerGame::new(4);
+        let hexes = vec![start_of(&game, 1)];
+        assert!(game.place(1, SINGLE, hexes).is_none());
+        assert!(game.pass(1).is_none());
+
+        let hexes = vec![start_of(&game, 0)];
+        assert_eq!(game.place(0, SINGLE, hexes.clone()), Some(NetMessage::Placed { player: 0, shape: SINGLE, hexes }));
+        assert_eq!(game.current_player, 1);
+    }
+
+    #[test]
+    fn blocks_of_another_shape_are_rejected() {
+        let mut game = ServerGame::new(4);
+        let single = vec![start_of(&game, 0)];
+        assert!(game.place(0, LINE_OF_FIVE, single).is_none());
+        let line = placement_at_start(&game, 0, &piece_shape(LINE_OF_FOUR), false);
+        assert!(game.place(0, ZIGZAG, line).is_none());
+        assert_eq!(game.current_player, 0);
+    }
+
+    #[test]
+    fn overlapping_placements_are_rejected() {
+        let mut game = ServerGame::new(2);
+        let single = vec![start_of(&game, 0)];
+        assert!(game.place(0, SINGLE, single.clone()).is_some());
+        assert!(game.pass(1).is_some());
+
+        let pair = placement_at_start(&ServerGame::new(2), 0, &piece_shape(PAIR), false);
+        assert!(pair.contains(&single[0]));
+        assert!(game.place(0, PAIR, pair).is_none());
+        // a shape can be placed only once
+        assert!(game.place(0, SINGLE, single).is_none());
+    }
+
+    #[test]
+    fn rotated_and_reflected_shapes_are_accepted() {
+        let zigzag = piece_shape(ZIGZAG);
+        for rotation in ALL_ROTATIONS {
+            let turned: Vec<Hex> = zigzag.iter().map(|block| block.rotate(rotation) + Hex { q: 3, r: -1 }).collect();
+            let flipped: Vec<Hex> = turned.iter().map(|block| block.reflect()).collect();
+            assert!(is_same_shape(&zigzag, &turned));
+            assert!(is_same_shape(&zigzag, &flipped));
+            assert!(!is_same_shape(&piece_shape(LINE_OF_FOUR), &flipped));
+        }
+
+        let mut game = ServerGame::new(4);
+        let hexes = placement_at_start(&game, 0, &zigzag, true);
+        assert!(game.place(0, ZIGZAG, hexes).is_some());
+    }
+
+    #[test]
+    fn game_is_over_once_everyone_passes_in_a_row() {
+        let mut game = ServerGame::new(3);
+        assert!(game.pass(0).is_some());
+        let hexes = vec![start_of(&game, 1)];
+        assert!(game.place(1, SINGLE, hexes).is_some());
+        // the placement started the count again
+        for player in [2, 0, 1] {
+            assert!(!game.is_over());
+            assert_eq!(game.pass(player), Some(NetMessage::Passed { player }));
+        }
+        assert!(game.is_over());
+
+        let hexes = vec![start_of(&game, 2)];
+        assert!(game.place(2, SINGLE, hexes).is_none());
+        assert!(game.pass(2).is_none());
+        assert_eq!(game.history.len(), 5);
+    }
+}
@@ -7,7 +7,9 @@ use crate::game::*;
 const BOARD_SECTOR: [i32; 11] = [0, 11, 10, 10, 9, 9, 8, 8, 6, 4, 2];
 const BOARD_SECTOR_SMALL: [i32; 8] = [0, 8, 7, 7, 6, 6, 4, 2];
 
-const ALL_PIECES: [&[(i32, i32)]; 11] =
+// blocks of every piece, the first one is the origin of the piece,
+// the trays are laid out by tray::layout_trays
+pub const ALL_PIECES: [&[(i32, i32)]; 11] =
 [
     // &[(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5), (0, 6), (0, 7), (0, 8), (0, 9), (0, 10), (0, 11), (0, 12), (0, 13), (0, 14)],
     // &[(2, 0), (2, 1), (2, 2), (2, 3), (2, 4), (2, 5), (2, 6), (2, 7), (2, 8), (2, 9), (2, 10), (2, 11), (2, 12), (2, 13), (2, 14)],
@@ -42,22 +44,19 @@ const ALL_PIECES: [&[(i32, i32)]; 11] =
 pub fn call(
     mut commands: Commands,
     mut game: ResMut<Game>,
+    patterns: Res<PlayerPatterns>,
     asset_server: Res<AssetServer>
 ) {
     let block_texture_handle = &asset_server.load("hex.png");
 
-    game.drop_audio_handles = vec![
-        asset_server.load("drop1.ogg"),
-        asset_server.load("drop2.ogg"),
-        asset_server.load("drop3.ogg"),
-        asset_server.load("drop4.ogg"),
-        asset_server.load("drop5.ogg")
-    ];
-
     let player_count = game.player_count;
+    // two players play on the four player board with all four sets
+    let piece_sets_count = if player_count == 2 { 4 } else { player_count };
 
-    fill_board(&mut game.board, true);
-    setup_board_for_players(&mut game.board, player_count);
+    game.board = build_board(player_count);
+    game.player_starts = (0..piece_sets_count).filter_map(|player_index|
+        game.board.iter().find_map(|(&hex, cell)| (*cell == Cell::PlayerStart(player_index)).then_some(hex))
+    ).collect();
 
     commands.spawn((OnGameScreen, BoardComponent, SpatialBundle::default())).with_children(|parent| {
         for &hex in game.board.keys() {
@@ -67,50 +66,82 @@ pub fn call(
                     BoardHex,
                     hex
                 )
-            );
+            ).with_children(|parent| {
+                // shown by board_pattern_system once a player covers the hex
+                parent.spawn((pattern_sprite(&patterns, 0, Color::WHITE), BoardPattern, hex, Visibility::Hidden));
+            });
         }
     });
 
-    let piece_sets_count = if player_count == 2 { 4 } else { player_count };
-
-    spawn_pieces(&mut commands, block_texture_handle, 0, Vec3 { x: 10.0 * HEX_WIDTH, y: -7.0 * HEX_WIDTH, z: 0.0  });
-    spawn_pieces(&mut commands, block_texture_handle, 1, Vec3 { x: -20.0 * HEX_WIDTH, y: -7.0 * HEX_WIDTH, z: 0.0  });
-    spawn_pieces(&mut commands, block_texture_handle, 2, Vec3 { x: -25.0 * HEX_WIDTH, y: 4.0 * HEX_WIDTH, z: 0.0  });
-    if piece_sets_count > 3 {
-        spawn_pieces(&mut commands, block_texture_handle, 3, Vec3 { x: -20.0 * HEX_WIDTH, y: 15.0 * HEX_WIDTH, z: 0.0  });
+    // enough ghost blocks to preview the largest piece
+    let ghost_blocks_count = ALL_PIECES.iter().map(|blocks| blocks.len()).max().unwrap_or(0);
+    for _ in 0..ghost_blocks_count {
+        commands.spawn((
+            OnGameScreen,
+            GhostBlock,
+            build_block_sprite(Hex { q: 0, r: 0 }, block_texture_handle, GHOST_LEGAL_COLOR)
+        )).insert(Visibility::Hidden);
     }
-    if piece_sets_count > 4 {
-        spawn_pieces(&mut commands, block_texture_handle, 4, Vec3 { x: 10.0 * HEX_WIDTH, y: 15.0 * HEX_WIDTH, z: 0.0  });
-        spawn_pieces(&mut commands, block_texture_handle, 5, Vec3 { x: 15.0 * HEX_WIDTH, y: 4.0 * HEX_WIDTH, z: 0.0  });
+
+    for player_index in 0..piece_sets_count {
+        let pattern = game.show_patterns.then(|| pattern_sprite(&patterns, player_index, game.player_color(player_index)));
+        spawn_pieces(&mut commands, block_texture_handle, &pattern, player_index, game.player_color(player_index));
     }
 }
 
-fn spawn_pieces(commands: &mut Commands, texture: &Handle<Image>, player_index: usize, starting_translation: Vec3) {
-    for piece_blocks in ALL_PIECES {
-        spawn_piece(commands, texture, player_index, piece_blocks, starting_translation);
+pub fn build_board(player_count: usize) -> Board {
+    let mut board = Board::new();
+    fill_board(&mut board, true);
+    setup_board_for_players(&mut board, player_count);
+    board
+}
+
+// blocks of the piece relative to its first block
+pub fn piece_shape(shape_index: usize) -> Vec<Hex> {
+    let blocks = ALL_PIECES[shape_index];
+    let base = Hex { q: blocks[0].0, r: blocks[0].1 };
+    blocks.iter().map(|&(q, r)| Hex { q, r } - base).collect()
+}
+
+fn spawn_pieces(
+    commands: &mut Commands,
+    texture: &Handle<Image>,
+    pattern: &Option<SpriteBundle>,
+    player_index: usize,
+    color: Color,
+) {
+    // moved into the tray by layout_trays
+    for shape_index in 0..ALL_PIECES.len() {
+        spawn_piece(commands, texture, pattern, player_index, color, shape_index, Transform::default());
     }
 }
 
-fn spawn_piece(commands: &mut Commands, texture: &Handle<Image>, player_index: usize, blocks: &[(i32, i32)], starting_translation: Vec3) {
+pub fn spawn_piece(commands: &mut Commands, texture: &Handle<Image>, pattern: &Option<SpriteBundle>, player_index: usize, color: Color, shape_index: usize, transform: Transform) -> Entity {
+    let blocks = ALL_PIECES[shape_index];
     let base = Hex { q: blocks[0].0, r: blocks[0].1 };
-    let translation = starting_translation + hex_to_pixel(&base).extend(0.0);
 
     commands.spawn((
         OnGameScreen,
         Piece(blocks.len()),
+        PieceShape(shape_index),
         PlayerIndex(player_index),
-        SpatialBundle { transform: Transform::from_translation(translation), ..default() }
+        SpatialBundle { transform, ..default() }
     )).with_children(|parent| {
         for tuple in blocks {
             let hex = Hex { q: tuple.0, r: tuple.1 };
             let relative_translation = hex - base;
-            parent.spawn((
-                build_block_sprite(relative_translation, texture, player_color(player_index)),
+            let mut block = parent.spawn((
+                build_block_sprite(relative_translation, texture, color),
                 BlockSelectable,
                 PlayerIndex(player_index)
             ));
+            if let Some(pattern) = pattern {
+                block.with_children(|parent| {
+                    parent.spawn(pattern.clone());
+                });
+            }
         }
-    });
+    }).id()
 }
 
 fn build_block_sprite(hex: Hex, texture: &Handle<Image>, color: Color) -> SpriteBundle {
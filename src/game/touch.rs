@@ -0,0 +1,89 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use crate::game::*;
+
+// twist of the second finger needed for one 60° step
+const TWIST_STEP: f32 = PI / 4.0;
+const TRAY_SCROLL_LIMIT: f32 = 8.0 * HEX_WIDTH;
+
+// second finger circling around the one that holds the piece rotates it
+pub fn touch_rotate(
+    touches: Res<Touches>,
+    game: Res<Game>,
+    mut twist_reference: Local<Option<f32>>,
+    mut ev_control: EventWriter<PieceControlEvent>,
+) {
+    let twist = game.touch_id
+        .and_then(|id| touches.get_pressed(id))
+        .zip(touches.iter().find(|touch| Some(touch.id()) != game.touch_id))
+        .map(|(holding, twisting)| {
+            let direction = twisting.position() - holding.position();
+            direction.y.atan2(direction.x)
+        });
+
+    let Some(angle) = twist else {
+        *twist_reference = None;
+        return;
+    };
+
+    let reference = *twist_reference.get_or_insert(angle);
+    // wrap into -PI..PI so crossing the negative x axis is not a full turn
+    let delta = (angle - reference + PI).rem_euclid(2.0 * PI) - PI;
+    if delta.abs() < TWIST_STEP {
+        return;
+    }
+
+    // screen y axis points down, so a positive angle is clockwise
+    if delta > 0.0 {
+        ev_control.send(PieceControlEvent::RotateCw);
+    } else {
+        ev_control.send(PieceControlEvent::RotateCcw);
+    }
+    *twist_reference = Some(angle);
+}
+
+// one finger dragging outside of the pieces scrolls the current player's tray
+pub fn touch_scroll_tray(
+    touches: Res<Touches>,
+    mut game: ResMut<Game>,
+    q_camera: Query<(&Camera, &GlobalTransform)>,
+    mut pieces: Query<(&mut Transform, &PlayerIndex), (With<Piece>, Without<Selected>)>,
+    mut scroll_touch_id: Local<Option<u64>>,
+) {
+    if game.touch_id.is_some() || game.hex_cursor.is_some() || touches.iter().count() != 1 {
+        *scroll_touch_id = None;
+        return;
+    }
+
+    if let Some(touch) = touches.iter_just_pressed().next() {
+        *scroll_touch_id = Some(touch.id());
+        return;
+    }
+
+    let Some(touch) = scroll_touch_id.and_then(|id| touches.get_pressed(id)) else {
+        return;
+    };
+
+    let (camera, camera_transform) = q_camera.single();
+    // trays scroll along the vertical axis of the screen, which turns with the camera
+    let screen_up = (camera_transform.affine().matrix3 * Vec3::Y).xy().normalize();
+    let world_delta = camera.viewport_to_world_2d(camera_transform, touch.position())
+        .zip(camera.viewport_to_world_2d(camera_transform, touch.previous_position()))
+        .map(|(position, previous_position)| (position - previous_position).dot(screen_up));
+    let Some(world_delta) = world_delta else {
+        return;
+    };
+
+    let current_player = game.current_player;
+    let scroll = &mut game.tray_scroll[current_player];
+    let new_scroll = (*scroll + world_delta).clamp(-TRAY_SCROLL_LIMIT, TRAY_SCROLL_LIMIT);
+    let applied_delta = new_scroll - *scroll;
+    *scroll = new_scroll;
+
+    for (mut transform, &PlayerIndex(player_index)) in &mut pieces {
+        if player_index == current_player {
+            transform.translation += (screen_up * applied_delta).extend(0.0);
+        }
+    }
+}
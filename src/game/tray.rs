@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::f32::consts::{SQRT_3, TAU};
+
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowResized};
+use crate::game::*;
+use crate::game::setup::{piece_shape, ALL_PIECES};
+
+// space between the pieces of a tray
+const PIECE_GAP: f32 = HEX_RADIUS;
+// space between the trays and the board or the edge of the view
+const TRAY_MARGIN: f32 = HEX_WIDTH;
+// button bar at the top of the screen in logical pixels, kept free of pieces
+const HUD_HEIGHT: f32 = 95.0;
+// row of tray summaries at the bottom of the screen in focus mode, in logical pixels
+const SUMMARY_HEIGHT: f32 = 40.0;
+// size of the pieces in the tray of the current player in focus mode
+const FOCUS_SCALE: f32 = 1.5;
+// higher than the patterns on the blocks so a piece never shows through another one
+const PIECE_LAYER: f32 = 0.2;
+// distance from the center of a hex to its edges
+const HEX_EXTENT: Vec2 = Vec2 { x: HEX_RADIUS, y: HEX_RADIUS * SQRT_3 / 2.0 };
+
+#[derive(Component)]
+pub struct TraySummaries;
+
+// pieces left in a collapsed tray
+#[derive(Component)]
+pub struct TraySummary(usize);
+
+// packs the pieces left in each tray into its region around the board as the view ends up turned,
+// again whenever the window is resized, a piece leaves or comes back to a tray,
+// another tray gets the focus or the view turns to another player
+pub fn layout_trays(
+    mut ev_resized: EventReader<WindowResized>,
+    mut removed_pieces: RemovedComponents<Piece>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut game: ResMut<Game>,
+    mut pieces: Query<(
+        Entity, Ref<Piece>, &PieceShape, &PlayerIndex, &mut Transform, &mut Visibility, Option<&Tween>, Has<Selected>, Has<PendingPlacement>
+    )>,
+    mut laid_out_view: Local<Option<(Option<usize>, f32)>>,
+    mut commands: Commands,
+) {
+    let focused = game.focus_tray.then_some(game.current_player);
+    let view_angle = target_view_angle(&game);
+    let resized = ev_resized.read().count() > 0;
+    let removed = removed_pieces.read().count() > 0;
+    let added = pieces.iter().any(|(_, piece, ..)| piece.is_added());
+    if !resized && !removed && !added && *laid_out_view == Some((focused, view_angle)) {
+        return;
+    }
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+    if window.width() <= 0.0 || window.height() <= 0.0 {
+        return;
+    }
+
+    let view_size = unzoomed_view_size(window);
+    let mut view = Rect::from_center_size(Vec2::ZERO, view_size);
+    view.max.y -= HUD_HEIGHT * view_size.y / window.height();
+    if focused.is_some() {
+        view.min.y += SUMMARY_HEIGHT * view_size.y / window.height();
+    }
+    let view = view.inset(-TRAY_MARGIN);
+    // the layout is done on the screen, the pieces turn with the view to stay upright on it
+    let view_rotation = Quat::from_rotation_z(view_angle);
+    let to_screen = |point: Vec2| (view_rotation.inverse() * point.extend(0.0)).xy();
+    let board_extent = game.board.keys().map(|hex| to_screen(hex_to_pixel(hex)).abs()).fold(Vec2::ZERO, Vec2::max) + HEX_EXTENT;
+    let starts: Vec<Vec2> = game.player_starts.iter().map(|start| to_screen(hex_to_pixel(start))).collect();
+    let regions = tray_regions(view, board_extent + TRAY_MARGIN, &starts);
+    *laid_out_view = Some((focused, view_angle));
+
+    let mut trays: Vec<Vec<(Entity, usize)>> = vec![Vec::new(); regions.len()];
+    let mut layers = HashMap::new();
+    for (entity, _, &PieceShape(shape), &PlayerIndex(player_index), ..) in &pieces {
+        if let Some(tray) = trays.get_mut(player_index) {
+            tray.push((entity, shape));
+        }
+        layers.insert(entity, (player_index * ALL_PIECES.len() + shape) as f32 * PIECE_LAYER);
+    }
+    // the focused tray takes the whole side of the board it is on, the other ones are hidden
+    let scale_of = |player_index| if focused == Some(player_index) { FOCUS_SCALE } else { 1.0 };
+    let positions: HashMap<Entity, Vec2> = trays
+        .iter_mut()
+        .zip(&regions)
+        .enumerate()
+        .flat_map(|(player_index, (tray, &(slot, side)))| {
+            tray.sort_by_key(|&(_, shape)| shape);
+            let region = if focused == Some(player_index) { side } else { slot };
+            pack_tray(tray, region, scale_of(player_index))
+        })
+        .collect();
+
+    // scrolled trays start over from the new layout
+    game.tray_scroll = default();
+    for (entity, piece, _, &PlayerIndex(player_index), mut transform, mut visibility, tween, held, pending) in &mut pieces {
+        let Some(position) = positions.get(&entity) else {
+            continue;
+        };
+        let target = Transform::from_translation(view_rotation * position.extend(DEFAULT_Z + layers[&entity]))
+            .with_rotation(view_rotation)
+            .with_scale(Vec3::splat(scale_of(player_index)));
+        // the piece in play goes back to its new place when it is returned
+        if held || pending {
+            game.original_transform = target;
+            continue;
+        }
+
+        let was_hidden = *visibility == Visibility::Hidden;
+        let shown = focused.is_none_or(|focused| focused == player_index);
+        *visibility = if shown { Visibility::Inherited } else { Visibility::Hidden };
+        if (piece.is_added() && tween.is_none()) || was_hidden || !shown {
+            *transform = target;
+            commands.entity(entity).remove::<Tween>();
+        } else if tween.map_or(*transform, |tween| tween.target) != target {
+            commands.entity(entity).insert(Tween::travel(*transform, target));
+        }
+    }
+}
+
+// the row of summaries shows the trays collapsed in focus mode
+pub fn spawn_tray_summaries(game: Res<Game>, mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Px(SUMMARY_HEIGHT),
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(0.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(30.0),
+                    ..default()
+                },
+                ..default()
+            },
+            TraySummaries,
+            OnGameScreen,
+        ))
+        .with_children(|parent| {
+            // sets nobody plays are never focused and not summed up
+            for player_index in 0..game.player_count {
+                parent.spawn((
+                    TextBundle::from_section("", TextStyle { font_size: 20.0, color: game.player_color(player_index), ..default() }),
+                    TraySummary(player_index),
+                ));
+            }
+        });
+}
+
+pub fn update_tray_summaries(
+    game: Res<Game>,
+    locale: Res<Locale>,
+    pieces: Query<&PlayerIndex, With<Piece>>,
+    mut summaries: Query<&mut Visibility, With<TraySummaries>>,
+    mut texts: Query<(&mut Text, &mut Style, &TraySummary)>,
+) {
+    for mut visibility in &mut summaries {
+        *visibility = if game.focus_tray { Visibility::Inherited } else { Visibility::Hidden };
+    }
+    if !game.focus_tray {
+        return;
+    }
+
+    for (mut text, mut style, &TraySummary(player_index)) in &mut texts {
+        style.display = if player_index == game.current_player { Display::None } else { Display::Flex };
+        let pieces_left = pieces.iter().filter(|&&PlayerIndex(index)| index == player_index).count();
+        text.sections[0].value = locale.plural("tray.pieces_left", pieces_left, &[("name", &game.player_name(player_index))]);
+    }
+}
+
+// one region per piece set, beside the board on a wide window and above and below it on a tall one,
+// paired with the whole side of the board it is on,
+// each set gets the region closest to its start corner
+fn tray_regions(view: Rect, board_extent: Vec2, starts: &[Vec2]) -> Vec<(Rect, Rect)> {
+    let count = starts.len();
+    let first_side = count.div_ceil(2);
+    let beside_area = (view.max.x - board_extent.x) * view.height();
+    let around_area = (view.max.y - board_extent.y).min(-board_extent.y - view.min.y) * view.width();
+
+    let split = |strip: Rect, parts: usize, vertical: bool| -> Vec<(Rect, Rect)> {
+        (0..parts).map(|part| {
+            let (from, to) = (part as f32 / parts as f32, (part + 1) as f32 / parts as f32);
+            let slot = if vertical {
+                let y = |fraction| strip.max.y - strip.height() * fraction;
+                Rect::new(strip.min.x, y(to), strip.max.x, y(from))
+            } else {
+                let x = |fraction| strip.min.x + strip.width() * fraction;
+                Rect::new(x(from), strip.min.y, x(to), strip.max.y)
+            };
+            (slot, strip)
+        }).collect()
+    };
+    let mut slots = if beside_area >= around_area {
+        let left = Rect::new(view.min.x, view.min.y, -board_extent.x, view.max.y);
+        let right = Rect::new(board_extent.x, view.min.y, view.max.x, view.max.y);
+        [split(left, first_side, true), split(right, count - first_side, true)].concat()
+    } else {
+        let top = Rect::new(view.min.x, board_extent.y, view.max.x, view.max.y);
+        let bottom = Rect::new(view.min.x, view.min.y, view.max.x, -board_extent.y);
+        [split(top, first_side, false), split(bottom, count - first_side, false)].concat()
+    };
+
+    // both go around the board in the same direction, only where the sets start is picked
+    let angle = |point: Vec2| point.y.atan2(point.x);
+    let angular_distance = |a: f32, b: f32| {
+        let difference = (a - b).rem_euclid(TAU);
+        difference.min(TAU - difference)
+    };
+    slots.sort_by(|(a, _), (b, _)| angle(a.center()).total_cmp(&angle(b.center())));
+    let mut sets: Vec<usize> = (0..count).collect();
+    sets.sort_by(|&a, &b| angle(starts[a]).total_cmp(&angle(starts[b])));
+    let cost = |shift: usize| -> f32 {
+        sets.iter().enumerate()
+            .map(|(index, &set)| angular_distance(angle(starts[set]), angle(slots[(index + shift) % count].0.center())))
+            .sum()
+    };
+    let shift = (0..count).min_by(|&a, &b| cost(a).total_cmp(&cost(b))).unwrap_or(0);
+
+    let mut regions = vec![(Rect::default(), Rect::default()); count];
+    for (index, &set) in sets.iter().enumerate() {
+        regions[set] = slots[(index + shift) % count];
+    }
+    regions
+}
+
+// rows of pieces filling the width of the region, centered in it,
+// a region too small for the tray lets it spill over its edges
+fn pack_tray(pieces: &[(Entity, usize)], region: Rect, scale: f32) -> Vec<(Entity, Vec2)> {
+    let mut rows: Vec<Vec<(Entity, Rect)>> = Vec::new();
+    let mut row_width = 0.0;
+    for &(entity, shape) in pieces {
+        let bounds = piece_shape(shape)
+            .iter()
+            .map(|hex| Rect::from_center_half_size(hex_to_pixel(hex) * scale, HEX_EXTENT * scale))
+            .reduce(|bounds, hex_bounds| bounds.union(hex_bounds))
+            .unwrap_or_default();
+        match rows.last_mut() {
+            Some(row) if row_width + PIECE_GAP + bounds.width() <= region.width() => {
+                row_width += PIECE_GAP + bounds.width();
+                row.push((entity, bounds));
+            }
+            _ => {
+                row_width = bounds.width();
+                rows.push(vec![(entity, bounds)]);
+            }
+        }
+    }
+
+    let row_height = |row: &Vec<(Entity, Rect)>| row.iter().map(|(_, bounds)| bounds.height()).fold(0.0, f32::max);
+    let total_height = rows.iter().map(row_height).sum::<f32>() + PIECE_GAP * (rows.len() as f32 - 1.0);
+    let mut top = region.center().y + total_height / 2.0;
+    let mut positions = Vec::new();
+    for row in &rows {
+        let width = row.iter().map(|(_, bounds)| bounds.width()).sum::<f32>() + PIECE_GAP * (row.len() as f32 - 1.0);
+        let mut left = region.center().x - width / 2.0;
+        for &(entity, bounds) in row {
+            positions.push((entity, Vec2 { x: left - bounds.min.x, y: top - bounds.max.y }));
+            left += bounds.width() + PIECE_GAP;
+        }
+        top -= row_height(row) + PIECE_GAP;
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::setup::build_board;
+
+    fn piece_bounds(shape: usize, position: Vec2, scale: f32) -> Rect {
+        piece_shape(shape)
+            .iter()
+            .map(|hex| Rect::from_center_half_size(position + hex_to_pixel(hex) * scale, HEX_EXTENT * scale))
+            .reduce(|bounds, hex_bounds| bounds.union(hex_bounds))
+            .unwrap()
+    }
+
+    #[test]
+    fn packed_pieces_fit_the_region_without_overlapping() {
+        let pieces: Vec<(Entity, usize)> = (0..ALL_PIECES.len()).map(|shape| (Entity::from_raw(shape as u32), shape)).collect();
+        let region = Rect::new(-900.0, -500.0, -300.0, 500.0);
+        for scale in [1.0, FOCUS_SCALE] {
+            let positions = pack_tray(&pieces, region, scale);
+            assert_eq!(positions.len(), pieces.len());
+
+            let bounds: Vec<Rect> = positions.iter()
+                .map(|&(entity, position)| piece_bounds(entity.index() as usize, position, scale))
+                .collect();
+            for (index, piece) in bounds.iter().enumerate() {
+                assert!(piece.min.x >= region.min.x - 0.01 && piece.max.x <= region.max.x + 0.01, "{piece:?}");
+                for other in &bounds[index + 1..] {
+                    assert!(piece.intersect(*other).is_empty(), "{piece:?} {other:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packed_rows_are_centered_in_the_region() {
+        let pieces = [(Entity::from_raw(0), 0)];
+        let region = Rect::new(100.0, -50.0, 300.0, 150.0);
+        let [(_, position)] = pack_tray(&pieces, region, 1.0)[..] else {
+            panic!("one piece is packed");
+        };
+        assert!(piece_bounds(0, position, 1.0).center().distance(region.center()) < 0.01);
+    }
+
+    // start corners of the sets as the game finds them on the board
+    fn starts(player_count: usize) -> Vec<Vec2> {
+        let board = build_board(player_count);
+        (0..player_count).filter_map(|player_index|
+            board.iter().find_map(|(hex, cell)| (*cell == Cell::PlayerStart(player_index)).then(|| hex_to_pixel(hex)))
+        ).collect()
+    }
+
+    #[test]
+    fn trays_go_beside_the_board_on_a_wide_view() {
+        let view = Rect::from_center_size(Vec2::ZERO, Vec2 { x: 1920.0, y: 1080.0 });
+        let board_extent = Vec2 { x: 500.0, y: 500.0 };
+        let starts = starts(4);
+        let regions = tray_regions(view, board_extent, &starts);
+        assert_eq!(regions.len(), starts.len());
+        for (&(slot, side), start) in regions.iter().zip(&starts) {
+            assert!(slot.max.x <= -board_extent.x || slot.min.x >= board_extent.x, "{slot:?}");
+            assert!(side.contains(slot.center()));
+            // the tray is on the side of the board its set starts on
+            assert_eq!(slot.center().x.signum(), start.x.signum());
+        }
+    }
+
+    #[test]
+    fn trays_go_above_and_below_the_board_on_a_tall_view() {
+        let view = Rect::from_center_size(Vec2::ZERO, Vec2 { x: 1080.0, y: 1920.0 });
+        let board_extent = Vec2 { x: 500.0, y: 500.0 };
+        let regions = tray_regions(view, board_extent, &starts(3));
+        assert_eq!(regions.len(), 3);
+        for &(slot, _) in &regions {
+            assert!(slot.max.y <= -board_extent.y || slot.min.y >= board_extent.y, "{slot:?}");
+        }
+        // no two sets share a region
+        for (index, (slot, _)) in regions.iter().enumerate() {
+            assert!(regions[index + 1..].iter().all(|(other, _)| other != slot));
+        }
+    }
+}
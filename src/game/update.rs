@@ -1,92 +1,147 @@
-use bevy::input::mouse::MouseWheel;
+use std::f32::consts::FRAC_PI_3;
+
 use bevy::prelude::*;
-use rand::seq::SliceRandom;
 use crate::{hex::Hex, CursorWorldCoords};
 use crate::game::*;
+use crate::game::controls::{Action, ActionState};
+use crate::game::hint::has_legal_placement;
+use crate::game::league::save_game_record;
+use crate::game::animation::{landing_cells, placed_transform, Landing, Tween, ROTATE_TIME};
+use crate::game::palette::{pattern_sprite, PlayerPatterns};
+use crate::game::setup::{spawn_piece, ALL_PIECES};
 
 #[derive(Debug)]
 struct PlayerStats {
     index: usize,
     blocks: usize,
-    largest_piece: usize
+    // sizes of the pieces left, largest first
+    piece_sizes: Vec<usize>,
+    pieces_placed: usize
 }
 
+impl PlayerStats {
+    // a player who placed every piece has no largest piece left
+    fn largest_piece(&self) -> usize {
+        self.piece_sizes.first().copied().unwrap_or(0)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn pickup_piece(
     world_cursor: Res<CursorWorldCoords>,
-    btn: Res<ButtonInput<MouseButton>>,
+    actions: Res<ActionState>,
+    touches: Res<Touches>,
     mut game: ResMut<Game>,
     selectable_blocks: Query<(&Parent, &GlobalTransform, &PlayerIndex), With<BlockSelectable>>,
     pieces: Query<(&Transform, &Children), (With<Piece>, Without<Selected>)>,
     selected_piece: Query<Entity, (With<Piece>, With<Selected>)>,
     mut commands: Commands,
 ) {
-    if !selected_piece.is_empty() || !btn.just_pressed(MouseButton::Left) {
+    let touch_id = match touches.iter().count() {
+        1 => touches.iter_just_pressed().next().map(|touch| touch.id()),
+        _ => None
+    };
+    // the pan modifier with left mouse button pans the camera
+    let pick_pressed = actions.just_pressed(Action::Pick) && !actions.pan_modifier_held();
+    if !selected_piece.is_empty() || !(pick_pressed || touch_id.is_some()) || !game.is_local_turn() {
         return;
     }
 
-    for (parent, child_transform, player_index) in selectable_blocks.iter() {
-        if game.current_player != player_index.0 {
-            continue;
-        }
-
-        if hex_collision_with_point(world_cursor.0, child_transform.translation()) {
-            let parent_piece_result = pieces.get(parent.get());
-            if let Ok(parent_piece) = parent_piece_result {
-                game.original_transform = *parent_piece.0;
-                game.mouse_offset = parent_piece.0.translation.xy() - world_cursor.0;
-
-                commands.entity(parent.get()).insert(Selected);
-                for child in parent_piece.1.iter() {
-                    commands.entity(*child).insert(Selected);
-                }
+    // overlapping pieces give the one drawn on top
+    let hit_block = selectable_blocks
+        .iter()
+        .filter(|(_, child_transform, player_index)|
+            game.current_player == player_index.0 && hex_collision_with_point(world_cursor.0, child_transform)
+        )
+        .max_by(|(_, a, _), (_, b, _)| a.translation().z.total_cmp(&b.translation().z));
+
+    if let Some((parent, _, _)) = hit_block {
+        let parent_piece_result = pieces.get(parent.get());
+        if let Ok(parent_piece) = parent_piece_result {
+            game.original_transform = *parent_piece.0;
+            // the offset on the board, where the piece is not scaled up like in a focused tray
+            game.mouse_offset = (parent_piece.0.translation.xy() - world_cursor.0) / parent_piece.0.scale.y;
+            game.touch_id = touch_id;
+
+            commands.entity(parent.get()).insert(Selected);
+            for child in parent_piece.1.iter() {
+                commands.entity(*child).insert(Selected);
             }
-            return;
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn move_piece(
-    mut scroll_evr: EventReader<MouseWheel>,
-    btn: Res<ButtonInput<KeyCode>>,
+    mut ev_control: EventReader<PieceControlEvent>,
+    actions: Res<ActionState>,
     world_cursor: Res<CursorWorldCoords>,
     mut game: ResMut<Game>,
-    mut selected_piece: Query<&mut Transform, (With<Piece>, With<Selected>)>,
-    mut selected_blocks: Query<(&GlobalTransform, &mut Sprite), (With<BlockSelectable>, With<Selected>)>,
+    mut selected_piece: Query<(Entity, &mut Transform, Option<&Tween>), (With<Piece>, With<Selected>)>,
+    mut selected_blocks: Query<(&Transform, &mut Sprite), (With<BlockSelectable>, With<Selected>, Without<Piece>)>,
+    q_camera: Query<&GlobalTransform, With<Camera>>,
+    mut commands: Commands,
 ) {
-    if let Ok(mut piece_transform) = selected_piece.get_single_mut() {
+    if let Ok((piece_entity, mut piece_transform, tween)) = selected_piece.get_single_mut() {
         let mut angle = 0.0_f32;
 
-        if btn.just_pressed(KeyCode::KeyR) {
-            if btn.pressed(KeyCode::ShiftLeft) {
-                angle = 60.0_f32.to_radians();
-            } else {
-                angle = -60.0_f32.to_radians();
-            }
+        if actions.just_pressed(Action::RotateCw) {
+            angle = -60.0_f32.to_radians();
+        } else if actions.just_pressed(Action::RotateCcw) {
+            angle = 60.0_f32.to_radians();
         }
 
-        for ev in scroll_evr.read() {
-            if ev.y > 0.0 {
-                angle = 60.0_f32.to_radians();
-            } else if ev.y < 0.0 {
-                angle = -60.0_f32.to_radians();
+        let mut flip = actions.just_pressed(Action::Flip);
+        for ev in ev_control.read() {
+            match ev {
+                PieceControlEvent::RotateCw => angle = -60.0_f32.to_radians(),
+                PieceControlEvent::RotateCcw => angle = 60.0_f32.to_radians(),
+                PieceControlEvent::Flip => flip = true,
+                PieceControlEvent::Place | PieceControlEvent::Cancel => ()
             }
         }
 
+        // the piece is shown turning towards this pose while it follows the cursor,
+        // at the size of the board
+        let mut pose = tween.map_or(*piece_transform, |tween| tween.target);
+        let previous_pose = pose;
+        pose.scale = pose.scale.signum();
         game.mouse_offset = Quat::from_rotation_z(angle).mul_vec3(game.mouse_offset.extend(0.0)).xy();
-        piece_transform.rotate(Quat::from_rotation_z(angle));
+        pose.rotate(Quat::from_rotation_z(angle));
+
+        // mirror around the vertical axis of the screen, of a turned view the closest axis of the grid
+        if flip {
+            let axis_turn = Quat::from_rotation_z((2.0 * view_angle(q_camera.single()) / FRAC_PI_3).round() * FRAC_PI_3);
+            let mirrored_offset = Vec2 { x: -game.mouse_offset.x, y: game.mouse_offset.y };
+            game.mouse_offset = (axis_turn * mirrored_offset.extend(0.0)).xy();
+            pose.rotation = axis_turn * pose.rotation.inverse();
+            pose.scale.x = -pose.scale.x;
+        }
+        if pose != previous_pose {
+            commands.entity(piece_entity).insert(Tween::new(*piece_transform, pose, ROTATE_TIME));
+        }
 
-        piece_transform.translation = Vec3 {
-            x: game.mouse_offset.x + world_cursor.0.x,
-            y: game.mouse_offset.y + world_cursor.0.y,
-            z: SELECTED_Z
+        let location = match game.hex_cursor {
+            Some(cursor) => hex_to_pixel(&cursor),
+            None => game.mouse_offset + world_cursor.0
         };
+        piece_transform.translation = location.extend(SELECTED_Z);
+        pose.translation = piece_transform.translation;
+
+        // the ghost preview and the drop both use these hexes
+        game.snapped_hexes = selected_blocks.iter().map(|(block_transform, _)|
+            pixel_to_hex(pose.transform_point(block_transform.translation).xy())
+        ).collect();
+    } else {
+        // turns and flips sent with nothing held do not carry over to the next piece picked up
+        ev_control.clear();
+        if !game.snapped_hexes.is_empty() {
+            game.snapped_hexes.clear();
+        }
     }
 
     if !selected_blocks.is_empty() {
-        let rounded_piece_blocks: Vec<Hex> = selected_blocks.iter().map(|(transform, _)|
-            pixel_to_hex(transform.translation().xy())
-        ).collect();
-        let piece_status = action_when_piece_placed(&game.board, &rounded_piece_blocks, game.current_player);
+        let piece_status = action_when_piece_placed(&game.board, &game.snapped_hexes, game.current_player);
         for (_, mut sprite) in selected_blocks.iter_mut() {
             let alpha = match piece_status {
                 PutPieceAction::ReturnToOrigin => 0.8,
@@ -98,60 +153,121 @@ pub fn move_piece(
 
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn put_piece(
-    btn: Res<ButtonInput<MouseButton>>,
+    actions: Res<ActionState>,
+    touches: Res<Touches>,
+    mut ev_control: EventReader<PieceControlEvent>,
+    mut ev_placement: EventWriter<PlacementRequestEvent>,
+    mut ev_sound: EventWriter<SoundEvent>,
     mut game: ResMut<Game>,
-    mut selected_blocks: Query<(Entity, &GlobalTransform, &mut Sprite), (With<BlockSelectable>, With<Selected>)>,
-    mut selected_piece: Query<(Entity, &mut Transform), (With<Piece>, With<Selected>)>,
+    mut selected_blocks: Query<(Entity, &mut Sprite), (With<BlockSelectable>, With<Selected>)>,
+    mut selected_piece: Query<(Entity, &mut Transform, &Children, &PieceShape), (With<Piece>, With<Selected>)>,
     mut commands: Commands,
 ) {
-    if !btn.just_released(MouseButton::Left) {
+    let mut placed = actions.just_pressed(Action::Drop);
+    let mut cancelled = actions.just_pressed(Action::Cancel);
+    for ev in ev_control.read() {
+        match ev {
+            PieceControlEvent::Place => placed = true,
+            PieceControlEvent::Cancel => cancelled = true,
+            _ => ()
+        }
+    }
+
+    let confirmed = match (game.hex_cursor, game.touch_id) {
+        (Some(_), _) => placed,
+        (None, Some(id)) => touches.just_released(id),
+        // a dragged piece drops where the pick input is let go
+        (None, None) => placed || actions.just_released(Action::Pick)
+    };
+    if let Some(id) = game.touch_id {
+        cancelled |= touches.just_canceled(id);
+    }
+    if !confirmed && !cancelled {
         return;
     }
 
-    if let Ok((piece_entity, mut piece_transform)) = selected_piece.get_single_mut() {
-        let rounded_piece_hexes: Vec<Hex> = selected_blocks.iter().map(|(_, transform, _)|
-            pixel_to_hex(transform.translation().xy())
-        ).collect();
-        let piece_status = action_when_piece_placed(&game.board, &rounded_piece_hexes, game.current_player);
+    if let Ok((piece_entity, mut piece_transform, blocks, &PieceShape(shape))) = selected_piece.get_single_mut() {
+        let rounded_piece_hexes = std::mem::take(&mut game.snapped_hexes);
+        let piece_status = if cancelled {
+            PutPieceAction::ReturnToOrigin
+        } else {
+            action_when_piece_placed(&game.board, &rounded_piece_hexes, game.current_player)
+        };
 
         match piece_status {
-            PutPieceAction::PutOnBoard => {
-                let current_player = game.current_player;
-                for hex in rounded_piece_hexes {
-                    game.board.insert(hex, Cell::Player(current_player));
-                }
-                for (piece_hex, _, _) in selected_blocks.iter() {
-                    commands.entity(piece_hex).despawn();
-                }
-                commands.entity(piece_entity).despawn();
-                game.pass_turn_count = 0;
-                game.current_player = (game.current_player + 1) % game.player_count;
-
-                if let Some(source) = game.drop_audio_handles.choose(&mut rand::thread_rng()) {
-                    commands.spawn(AudioBundle {
-                        source: source.clone(),
-                        settings: PlaybackSettings::DESPAWN
-                    });
+            PutPieceAction::PutOnBoard if game.seat.is_some() => {
+                // the piece waits on its hexes until the server accepts the move
+                if let Some(pose) = placed_transform(shape, &rounded_piece_hexes, &piece_transform) {
+                    commands.entity(piece_entity).insert((Tween::travel(*piece_transform, pose), PendingPlacement));
                 }
+                ev_placement.send(PlacementRequestEvent { shape, hexes: rounded_piece_hexes });
+            },
+            PutPieceAction::PutOnBoard => {
+                apply_placement(&mut commands, &mut ev_sound, &mut game, Some((piece_entity, &piece_transform, blocks)), shape, &rounded_piece_hexes);
             },
             PutPieceAction::ReturnToOrigin => {
-                piece_transform.translation = game.original_transform.translation;
-                piece_transform.rotation = game.original_transform.rotation;
+                commands.entity(piece_entity).insert(Tween::travel(*piece_transform, game.original_transform));
+                if !cancelled {
+                    ev_sound.send(SoundEvent(Cue::IllegalDrop));
+                }
             },
+            // back to its slot, the tray kept it free while the piece was held
             PutPieceAction::PutOutsideBoard => {
+                commands.entity(piece_entity).insert(Tween::travel(*piece_transform, game.original_transform));
             }
         }
 
-        for (block, _, mut sprite) in selected_blocks.iter_mut() {
+        for (block, mut sprite) in selected_blocks.iter_mut() {
             sprite.color = sprite.color.with_a(1.0);
             piece_transform.translation.z = DEFAULT_Z;
             commands.entity(block).remove::<Selected>();
         }
         commands.entity(piece_entity).remove::<Selected>();
+        game.hex_cursor = None;
+        game.touch_id = None;
     }
 }
 
+// the piece leaves the tray right away and flies to its hexes,
+// without a piece only the board and the turn change
+pub fn apply_placement(
+    commands: &mut Commands,
+    ev_sound: &mut EventWriter<SoundEvent>,
+    game: &mut Game,
+    piece: Option<(Entity, &Transform, &Children)>,
+    shape: usize,
+    hexes: &[Hex],
+) {
+    let current_player = game.current_player;
+    let cells: Vec<(Hex, Cell)> = hexes.iter().filter_map(|&hex| game.board.insert(hex, Cell::Player(current_player)).map(|cell| (hex, cell))).collect();
+    if let Some((piece_entity, piece_transform, blocks)) = piece {
+        let start = Transform { translation: piece_transform.translation.xy().extend(SELECTED_Z), ..*piece_transform };
+        let target = placed_transform(shape, hexes, piece_transform).unwrap_or(start);
+        commands.entity(piece_entity)
+            .remove::<(Piece, PieceShape, PlayerIndex, PendingPlacement)>()
+            // shown even when its tray was collapsed
+            .insert((Tween::travel(start, target), Landing(cells.clone()), Visibility::Inherited));
+        for &block in blocks.iter() {
+            commands.entity(block).remove::<(BlockSelectable, PlayerIndex)>();
+        }
+    }
+
+    game.undo_moves.push(UndoMove::Placed {
+        player: current_player,
+        shape,
+        cells,
+        tray_transform: game.original_transform,
+        pass_turn_count: game.pass_turn_count,
+    });
+    game.move_history.push(MoveRecord::Placed { player: current_player, blocks: hexes.len() });
+    game.pass_turn_count = 0;
+    game.current_player = (game.current_player + 1) % game.player_count;
+
+    ev_sound.send(SoundEvent(Cue::Drop));
+}
+
 fn action_when_piece_placed(board: &Board, piece_blocks: &[Hex], current_player: usize) -> PutPieceAction {
     let outside_board = piece_blocks.iter().all(|hex| !board.contains_key(hex));
 
@@ -164,7 +280,7 @@ fn action_when_piece_placed(board: &Board, piece_blocks: &[Hex], current_player:
     }
 }
 
-fn piece_can_be_placed_on_board(board: &Board, piece_blocks: &[Hex], current_player: usize) -> bool {
+pub fn piece_can_be_placed_on_board(board: &Board, piece_blocks: &[Hex], current_player: usize) -> bool {
     for hex in piece_blocks {
         match board.get(hex) {
             // always can place on own starting square
@@ -192,11 +308,42 @@ fn piece_can_be_placed_on_board(board: &Board, piece_blocks: &[Hex], current_pla
 }
 
 
-fn is_hex_belong_to_player(board: &Board, hex: Hex, player_index: usize) -> bool {
+// empty cells where the player can start a placement:
+// diagonal contact with own piece and no edge contact with it
+pub fn corner_cells(board: &Board, player_index: usize) -> Vec<Hex> {
+    board
+        .keys()
+        .copied()
+        .filter(|&hex| is_corner_cell(board, hex, player_index))
+        .collect()
+}
+
+fn is_corner_cell(board: &Board, hex: Hex, player_index: usize) -> bool {
+    match board.get(&hex) {
+        Some(Cell::PlayerStart(index)) if *index == player_index => return true,
+        Some(Cell::Empty | Cell::PlayerStart(_)) => (),
+        _ => return false
+    }
+
+    if NEIGHBOURS.into_iter().any(|n| is_hex_belong_to_player(board, hex + n, player_index)) {
+        return false;
+    }
+
+    DIAGONAL_NEIGHBOURS.into_iter().any(|(diagonal, near_1, near_2)|
+        is_hex_belong_to_player(board, hex + diagonal, player_index) &&
+        is_hexes_belong_to_different_players(board, hex + near_1, hex + near_2)
+    )
+}
+
+pub fn is_hex_free(board: &Board, hex: &Hex) -> bool {
+    matches!(board.get(hex), Some(Cell::Empty | Cell::PlayerStart(_)))
+}
+
+pub fn is_hex_belong_to_player(board: &Board, hex: Hex, player_index: usize) -> bool {
     matches!(board.get(&hex), Some(Cell::Player(i)) if *i == player_index)
 }
 
-fn is_hexes_belong_to_different_players(board: &Board, hex1: Hex, hex2: Hex) -> bool {
+pub fn is_hexes_belong_to_different_players(board: &Board, hex1: Hex, hex2: Hex) -> bool {
     match (board.get(&hex1), board.get(&hex2)) {
         (Some(Cell::Player(i)), Some(Cell::Player(j))) => i != j,
         _ => true
@@ -205,27 +352,35 @@ fn is_hexes_belong_to_different_players(board: &Board, hex1: Hex, hex2: Hex) ->
 
 pub fn board_system(
     mut board_hexes: Query<(&mut Sprite, &Hex), With<BoardHex>>,
+    landing: Query<&Landing>,
     game: Res<Game>,
-    selected_blocks: Query<&GlobalTransform, (With<BlockSelectable>, With<Selected>)>,
 ) {
-    let selected_hexes: Vec<Hex> = selected_blocks.iter().map(|transform|
-        pixel_to_hex(transform.translation().xy())
-    ).collect();
+    let landing_cells = landing_cells(&landing);
+    let corners = if game.show_corners {
+        corner_cells(&game.board, game.current_player)
+    } else {
+        Vec::new()
+    };
 
     for (mut sprite, hex) in &mut board_hexes {
-        if selected_hexes.contains(hex) && piece_can_be_placed_on_board(&game.board, &selected_hexes, game.current_player) {
-            sprite.color = Color::GRAY;
+        if let Some(hint) = game.hint.as_ref().filter(|hint| hint.hexes.contains(hex)) {
+            sprite.color = game.player_color_light(hint.player);
+            continue;
+        }
+
+        if corners.contains(hex) {
+            sprite.color = game.player_color_pale(game.current_player);
             continue;
         }
 
-        if let Some(cell) = game.board.get(hex) {
+        if let Some(cell) = landing_cells.get(hex).or_else(|| game.board.get(hex)) {
             match cell {
                 Cell::Empty => sprite.color = Color::WHITE,
                 Cell::Player(i) => {
-                    sprite.color = player_color(*i);
+                    sprite.color = game.player_color(*i);
                 }
                 Cell::PlayerStart(i) => {
-                    sprite.color = player_color_darken(*i);
+                    sprite.color = game.player_color_darken(*i);
                 }
                 Cell::Disabled => sprite.color = Color::DARK_GRAY
             };
@@ -233,16 +388,59 @@ pub fn board_system(
     }
 }
 
-pub fn on_pass_turn(
+pub fn ghost_system(
+    game: Res<Game>,
+    mut ghost_blocks: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<GhostBlock>>,
+) {
+    let color = match action_when_piece_placed(&game.board, &game.snapped_hexes, game.current_player) {
+        PutPieceAction::PutOnBoard => Some(GHOST_LEGAL_COLOR),
+        PutPieceAction::ReturnToOrigin => Some(GHOST_ILLEGAL_COLOR),
+        PutPieceAction::PutOutsideBoard => None
+    };
+
+    let mut hexes = game.snapped_hexes.iter();
+    for (mut transform, mut sprite, mut visibility) in &mut ghost_blocks {
+        match (hexes.next(), color) {
+            (Some(hex), Some(color)) => {
+                transform.translation = hex_to_pixel(hex).extend(GHOST_Z);
+                sprite.color = color;
+                *visibility = Visibility::Visible;
+            }
+            _ => *visibility = Visibility::Hidden
+        }
+    }
+}
+
+// without a server a pass is applied right away
+pub fn request_pass(
     mut ev_pass: EventReader<PassTurnEvent>,
+    mut ev_turn_passed: EventWriter<TurnPassedEvent>,
+    game: Res<Game>,
+) {
+    for _ev in ev_pass.read() {
+        if game.seat.is_none() && game.is_local_turn() {
+            ev_turn_passed.send(TurnPassedEvent);
+        }
+    }
+}
+
+pub fn on_pass_turn(
+    mut ev_pass: EventReader<TurnPassedEvent>,
+    mut ev_sound: EventWriter<SoundEvent>,
     mut game: ResMut<Game>,
     mut game_state: ResMut<NextState<GameState>>,
+    mut current_match: Option<ResMut<Match>>,
     blocks: Query<(&BlockSelectable, &PlayerIndex)>,
     pieces: Query<(&Piece, &PlayerIndex)>,
 ) {
     for _ev in ev_pass.read() {
+        let player = game.current_player;
+        let pass_turn_count = game.pass_turn_count;
+        game.undo_moves.push(UndoMove::Passed { player, pass_turn_count });
+        game.move_history.push(MoveRecord::Passed { player });
         game.current_player = (game.current_player + 1) % game.player_count;
         game.pass_turn_count += 1;
+        ev_sound.send(SoundEvent(Cue::Pass));
         let players_have_turns = game.pass_turn_count < game.player_count;
         if players_have_turns {
             continue
@@ -252,14 +450,86 @@ pub fn on_pass_turn(
 
         let players_stats: Vec<PlayerStats> = players_stats(game.player_count, &blocks, &pieces);
 
-        game.winner_player = detect_winner(players_stats);
+        let (winner_player, decided_by) = detect_winner(&players_stats);
+        game.winner_player = winner_player;
+        let results = game_results(&players_stats, decided_by);
+        if let Some(current_match) = current_match.as_deref_mut() {
+            current_match.record(&results);
+        }
+        save_game_record(&game, &results);
+        game.results = Some(results);
+    }
+}
 
-        if let Some(index) = game.winner_player {
-            println!("Winner is {index:?}");
-        } else {
-            println!("No winner");
+// held pieces, whichever input picked them
+pub fn announce_pickup(picked: Query<(), (With<Piece>, Added<Selected>)>, mut ev_sound: EventWriter<SoundEvent>) {
+    if !picked.is_empty() {
+        ev_sound.send(SoundEvent(Cue::Pickup));
+    }
+}
+
+// a chime when the turn goes to the next player, a lower one when that player has no piece that fits
+pub fn announce_turn(
+    game: Res<Game>,
+    pieces: Query<(&PieceShape, &PlayerIndex), With<Piece>>,
+    mut announced_player: Local<Option<usize>>,
+    mut ev_sound: EventWriter<SoundEvent>,
+) {
+    // the first player of a new game starts without a sound
+    if game.is_added() {
+        *announced_player = Some(game.current_player);
+        return;
+    }
+    if *announced_player == Some(game.current_player) || game.results.is_some() {
+        return;
+    }
+    *announced_player = Some(game.current_player);
+
+    let shapes = pieces
+        .iter()
+        .filter(|(_, &PlayerIndex(player_index))| player_index == game.current_player)
+        .map(|(&PieceShape(shape), _)| shape);
+    let cue = if has_legal_placement(&game.board, game.current_player, shapes) { Cue::TurnChange } else { Cue::OutOfMoves };
+    ev_sound.send(SoundEvent(cue));
+}
+
+// only in local games, the server keeps the moves of a network game
+pub fn undo_move(
+    mut ev_undo: EventReader<UndoEvent>,
+    mut game: ResMut<Game>,
+    patterns: Res<PlayerPatterns>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for _ev in ev_undo.read() {
+        if game.is_network_game() {
+            continue;
         }
+        let Some(undo) = game.undo_moves.pop() else {
+            continue;
+        };
+        game.move_history.pop();
 
+        match undo {
+            UndoMove::Placed { player, shape, cells, tray_transform, pass_turn_count } => {
+                let hexes: Vec<Hex> = cells.iter().map(|&(hex, _)| hex).collect();
+                for (hex, cell) in cells {
+                    game.board.insert(hex, cell);
+                }
+                // back from the board to the tray
+                let pattern = game.show_patterns.then(|| pattern_sprite(&patterns, player, game.player_color(player)));
+                let texture = asset_server.load("hex.png");
+                let start = placed_transform(shape, &hexes, &tray_transform).unwrap_or(tray_transform);
+                let piece = spawn_piece(&mut commands, &texture, &pattern, player, game.player_color(player), shape, start);
+                commands.entity(piece).insert(Tween::travel(start, tray_transform));
+                game.current_player = player;
+                game.pass_turn_count = pass_turn_count;
+            }
+            UndoMove::Passed { player, pass_turn_count } => {
+                game.current_player = player;
+                game.pass_turn_count = pass_turn_count;
+            }
+        }
     }
 }
 
@@ -276,52 +546,77 @@ fn players_stats(
                 .filter(|(_, &PlayerIndex(player_index))| player_index == i)
                 .count();
 
-        let smallest_piece_option =
-            pieces
-                .iter()
-                .filter(|(_, &PlayerIndex(player_index))| player_index == i)
-                .map(|(&Piece(size), _)| size)
-                .max();
+        let player_pieces = || pieces
+            .iter()
+            .filter(|(_, &PlayerIndex(player_index))| player_index == i);
 
-        if let Some(smallest_piece) = smallest_piece_option {
-            players_stats.push(PlayerStats { index: i, blocks: blocks_count, largest_piece: smallest_piece });
-        }
+        let mut piece_sizes: Vec<usize> = player_pieces().map(|(&Piece(size), _)| size).collect();
+        piece_sizes.sort_unstable_by(|a, b| b.cmp(a));
+        let pieces_placed = ALL_PIECES.len() - piece_sizes.len();
+
+        players_stats.push(PlayerStats { index: i, blocks: blocks_count, piece_sizes, pieces_placed });
     }
     players_stats
 }
 
-fn hex_collision_with_point(point: Vec2, translation: Vec3) -> bool{
-    translation.xy().distance_squared(point) <= HEX_RADIUS * HEX_RADIUS
+// players with fewer blocks left rank higher, then the ones with a smaller largest piece
+fn game_results(players_stats: &[PlayerStats], decided_by: TiebreakRule) -> GameResults {
+    // same order as the rules of detect_winner
+    let score = |stats: &PlayerStats| (stats.blocks, stats.piece_sizes.clone());
+    let mut players: Vec<PlayerResult> = players_stats
+        .iter()
+        .map(|stats| PlayerResult {
+            player: stats.index,
+            remaining_blocks: stats.blocks,
+            largest_piece: stats.largest_piece(),
+            pieces_placed: stats.pieces_placed,
+            rank: 1 + players_stats.iter().filter(|other| score(other) < score(stats)).count(),
+        })
+        .collect();
+    players.sort_by_key(|result| (result.rank, result.player));
+
+    GameResults { players, decided_by }
+}
+
+// blocks of a focused tray are larger than on the board
+fn hex_collision_with_point(point: Vec2, block_transform: &GlobalTransform) -> bool {
+    let radius = HEX_RADIUS * block_transform.compute_transform().scale.y / HEX_SCALE;
+    block_transform.translation().xy().distance_squared(point) <= radius * radius
 }
 
 fn detect_winner(
-    mut players_stats: Vec<PlayerStats>,
-) -> Option<usize> {
-    println!("Players stats: {players_stats:?}");
+    players_stats: &[PlayerStats],
+) -> (Option<usize>, TiebreakRule) {
+    let mut players_stats: Vec<&PlayerStats> = players_stats.iter().collect();
     if players_stats.is_empty() {
-        println!("No players with pieces");
-        return None;
+        return (None, TiebreakRule::Draw);
     }
 
     // rule 1
     let minimum_blocks = players_stats.iter().map(|s| s.blocks).min().unwrap();
     players_stats.retain(|s| s.blocks == minimum_blocks);
-    println!("Players stats with minimum number of blocks: {players_stats:?}");
     if players_stats.len() == 1 {
         let player_stat = players_stats.first().unwrap();
-        return Some(player_stat.index);
+        return (Some(player_stat.index), TiebreakRule::FewestBlocks);
     }
 
     // rule 2
-    let smallest_largest_piece = players_stats.iter().map(|s| s.largest_piece).min().unwrap();
-    players_stats.retain(|s| s.largest_piece == smallest_largest_piece);
-    println!("Players stats with smallest largest piece: {players_stats:?}");
+    let smallest_largest_piece = players_stats.iter().map(|s| s.largest_piece()).min().unwrap();
+    players_stats.retain(|s| s.largest_piece() == smallest_largest_piece);
+    if players_stats.len() == 1 {
+        let player_stat = players_stats.first().unwrap();
+        return (Some(player_stat.index), TiebreakRule::SmallestLargestPiece);
+    }
+
+    // rule 3, the value of the pieces left, compared from the largest piece down
+    let lowest_piece_sizes = players_stats.iter().map(|s| s.piece_sizes.clone()).min().unwrap();
+    players_stats.retain(|s| s.piece_sizes == lowest_piece_sizes);
     if players_stats.len() == 1 {
         let player_stat = players_stats.first().unwrap();
-        return Some(player_stat.index);
+        return (Some(player_stat.index), TiebreakRule::PieceValues);
     }
 
-    todo!("rule 3 not implemented");
+    (None, TiebreakRule::Draw)
 }
 
 // fn is_game_end(player_index: usize, board: &Board) {
@@ -379,3 +674,91 @@ fn detect_winner(
 
 //     false
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every hex of a parallelogram around the origin is empty
+    fn empty_board(size: i32) -> Board {
+        (-size..=size).flat_map(|q| (-size..=size).map(move |r| (Hex { q, r }, Cell::Empty))).collect()
+    }
+
+    #[test]
+    fn corner_cells_are_diagonal_to_own_blocks_and_never_on_their_edges() {
+        let mut board = empty_board(6);
+        for hex in [Hex { q: 0, r: 0 }, Hex { q: 1, r: 0 }, Hex { q: 1, r: 1 }] {
+            board.insert(hex, Cell::Player(0));
+        }
+        for hex in [Hex { q: -2, r: 1 }, Hex { q: -3, r: 1 }] {
+            board.insert(hex, Cell::Player(1));
+        }
+
+        let corners = corner_cells(&board, 0);
+        assert!(!corners.is_empty());
+        for hex in &corners {
+            assert_eq!(board.get(hex), Some(&Cell::Empty));
+            assert!(NEIGHBOURS.into_iter().all(|n| !is_hex_belong_to_player(&board, *hex + n, 0)), "{hex:?} is on an edge");
+            assert!(DIAGONAL_NEIGHBOURS.into_iter().any(|(diagonal, _, _)| is_hex_belong_to_player(&board, *hex + diagonal, 0)));
+        }
+        // diagonal to the block at the origin
+        assert!(corners.contains(&Hex { q: -1, r: -1 }));
+    }
+
+    #[test]
+    fn the_start_is_the_only_corner_before_the_first_move() {
+        let mut board = empty_board(3);
+        board.insert(Hex { q: 2, r: -1 }, Cell::PlayerStart(0));
+        board.insert(Hex { q: -2, r: 1 }, Cell::PlayerStart(1));
+        assert_eq!(corner_cells(&board, 0), [Hex { q: 2, r: -1 }]);
+    }
+
+    fn stats(index: usize, piece_sizes: &[usize]) -> PlayerStats {
+        PlayerStats { index, blocks: piece_sizes.iter().sum(), piece_sizes: piece_sizes.to_vec(), pieces_placed: ALL_PIECES.len() - piece_sizes.len() }
+    }
+
+    #[test]
+    fn fewest_blocks_left_wins() {
+        let players = [stats(0, &[3, 2]), stats(1, &[2, 1]), stats(2, &[4])];
+        assert_eq!(detect_winner(&players), (Some(1), TiebreakRule::FewestBlocks));
+
+        let results = game_results(&players, TiebreakRule::FewestBlocks);
+        let ranks: Vec<(usize, usize)> = results.players.iter().map(|result| (result.player, result.rank)).collect();
+        assert_eq!(ranks, [(1, 1), (2, 2), (0, 3)]);
+        assert_eq!(results.players[0].remaining_blocks, 3);
+        assert_eq!(results.players[0].largest_piece, 2);
+    }
+
+    #[test]
+    fn equal_blocks_go_to_the_smallest_largest_piece() {
+        let players = [stats(0, &[4, 1]), stats(1, &[3, 2]), stats(2, &[6])];
+        assert_eq!(detect_winner(&players), (Some(1), TiebreakRule::SmallestLargestPiece));
+
+        let results = game_results(&players, TiebreakRule::SmallestLargestPiece);
+        let order: Vec<usize> = results.players.iter().map(|result| result.player).collect();
+        assert_eq!(order, [1, 0, 2]);
+    }
+
+    #[test]
+    fn equal_largest_pieces_go_to_the_lower_piece_values() {
+        let players = [stats(0, &[3, 3]), stats(1, &[3, 2, 1]), stats(2, &[5, 1])];
+        assert_eq!(detect_winner(&players), (Some(1), TiebreakRule::PieceValues));
+
+        let results = game_results(&players, TiebreakRule::PieceValues);
+        let ranks: Vec<(usize, usize)> = results.players.iter().map(|result| (result.player, result.rank)).collect();
+        assert_eq!(ranks, [(1, 1), (0, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn same_pieces_left_is_a_draw() {
+        let players = [stats(0, &[3, 1]), stats(1, &[4]), stats(2, &[3, 1])];
+        assert_eq!(detect_winner(&players), (None, TiebreakRule::Draw));
+
+        // the tied players share the rank
+        let results = game_results(&players, TiebreakRule::Draw);
+        let ranks: Vec<(usize, usize)> = results.players.iter().map(|result| (result.player, result.rank)).collect();
+        assert_eq!(ranks, [(0, 1), (2, 1), (1, 3)]);
+        // with every piece placed nothing is left to compare
+        assert_eq!(detect_winner(&[stats(0, &[]), stats(1, &[])]), (None, TiebreakRule::Draw));
+    }
+}
@@ -31,6 +31,11 @@ impl Hex {
         self.to_cube().rotate(rotation).to_hex()
     }
 
+    // mirror around the q = 0 column
+    pub fn reflect(self) -> Self {
+        Self { q: -self.q, r: self.r + self.q }
+    }
+
     pub fn from_fraction(q: f32, r: f32) -> Self {
         let s = -q - r;
         HexCube::from_fraction(q, r, s).to_hex()
@@ -97,4 +102,37 @@ impl HexCube {
 
         Self { q, r, s }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some_hexes() -> impl Iterator<Item = Hex> {
+        (-3..=3).flat_map(|q| (-3..=3).map(move |r| Hex { q, r }))
+    }
+
+    #[test]
+    fn reflecting_twice_is_identity() {
+        for hex in some_hexes() {
+            assert_eq!(hex.reflect().reflect(), hex);
+        }
+    }
+
+    #[test]
+    fn reflection_turns_rotations_the_other_way() {
+        let inverses = [
+            (Rotation::Rot0, Rotation::Rot0),
+            (Rotation::Rot60Cw, Rotation::Rot60Ccw),
+            (Rotation::Rot60Ccw, Rotation::Rot60Cw),
+            (Rotation::Rot120Cw, Rotation::Rot120Ccw),
+            (Rotation::Rot120Ccw, Rotation::Rot120Cw),
+            (Rotation::Rot180, Rotation::Rot180),
+        ];
+        for hex in some_hexes() {
+            for (rotation, inverse) in inverses {
+                assert_eq!(hex.rotate(rotation).reflect(), hex.reflect().rotate(inverse));
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::prelude::*;
+
+use crate::settings::Settings;
+
+pub const DEFAULT_LANGUAGE: &str = "en";
+// built in so that every text has a fallback even without the assets
+const ENGLISH: &str = include_str!("../assets/locales/en.txt");
+// the font built into bevy has only ASCII letters
+const FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+
+// texts of the language picked in the settings with English for missing keys
+#[derive(Resource)]
+pub struct Locale {
+    language: String,
+    texts: HashMap<String, String>,
+    english: HashMap<String, String>,
+    // codes and names of the languages found in the locales directory
+    languages: Vec<(String, String)>,
+}
+
+// text of a label that changes only with the language
+#[derive(Component)]
+pub struct LocalizedText(pub &'static str);
+
+impl Locale {
+    pub fn load(language: &str) -> Self {
+        let english = parse_texts(ENGLISH);
+        let english_name = english.get("language").cloned().unwrap_or_else(|| DEFAULT_LANGUAGE.to_string());
+        let mut languages = vec![(DEFAULT_LANGUAGE.to_string(), english_name)];
+        if let Ok(entries) = fs::read_dir(locales_dir()) {
+            let mut found: Vec<(String, String)> = entries
+                .filter_map(|entry| entry.ok()?.path().file_stem()?.to_str().map(str::to_string))
+                .filter(|code| code != DEFAULT_LANGUAGE)
+                .filter_map(|code| {
+                    let name = read_texts(&code)?.remove("language")?;
+                    Some((code, name))
+                })
+                .collect();
+            found.sort();
+            languages.extend(found);
+        }
+
+        let texts = match language {
+            DEFAULT_LANGUAGE => HashMap::new(),
+            _ => read_texts(language).unwrap_or_default()
+        };
+        Self { language: language.to_string(), texts, english, languages }
+    }
+
+    pub fn language_name(&self) -> &str {
+        self.languages.iter().find(|(code, _)| *code == self.language).map_or(&self.language, |(_, name)| name)
+    }
+
+    // the language after the current one, wrapping around
+    pub fn next_language(&self) -> String {
+        let index = self.languages.iter().position(|(code, _)| *code == self.language).unwrap_or(0);
+        self.languages[(index + 1) % self.languages.len()].0.clone()
+    }
+
+    // the key itself when even English misses it
+    pub fn get(&self, key: &str) -> String {
+        self.texts.get(key).or_else(|| self.english.get(key)).cloned().unwrap_or_else(|| key.to_string())
+    }
+
+    // `{name}` in the text is replaced by the value of the argument `name`
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        fill_arguments(self.get(key), args)
+    }
+
+    // picks the plural form of `key` for `count`, which is also available as `{count}`
+    pub fn plural(&self, key: &str, count: usize, args: &[(&str, &dyn Display)]) -> String {
+        let translated = self.texts.get(&format!("{key}.{}", plural_form(&self.language, count)))
+            .or_else(|| self.texts.get(&format!("{key}.other")));
+        let text = translated
+            .or_else(|| self.english.get(&format!("{key}.{}", plural_form(DEFAULT_LANGUAGE, count))))
+            .cloned()
+            .unwrap_or_else(|| key.to_string());
+        let mut all_args: Vec<(&str, &dyn Display)> = vec![("count", &count)];
+        all_args.extend_from_slice(args);
+        fill_arguments(text, &all_args)
+    }
+
+    // name of a seat whose player did not type one
+    pub fn default_player_name(&self, seat: usize) -> String {
+        self.format("player.default", &[("n", &(seat + 1))])
+    }
+
+    pub fn on_off(&self, value: bool) -> String {
+        self.get(if value { "settings.on" } else { "settings.off" })
+    }
+}
+
+fn fill_arguments(mut text: String, args: &[(&str, &dyn Display)]) -> String {
+    for (name, value) in args {
+        text = text.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    text
+}
+
+// CLDR plural categories of the cardinal numbers in the shipped languages
+fn plural_form(language: &str, count: usize) -> &'static str {
+    let (last_digit, last_two_digits) = (count % 10, count % 100);
+    let few = (2..=4).contains(&last_digit) && !(12..=14).contains(&last_two_digits);
+    match language {
+        "pl" if count == 1 => "one",
+        "pl" if few => "few",
+        "pl" => "many",
+        "fr" if count <= 1 => "one",
+        _ if count == 1 => "one",
+        _ => "other"
+    }
+}
+
+fn locales_dir() -> PathBuf {
+    FileAssetReader::get_base_path().join("assets").join("locales")
+}
+
+fn read_texts(language: &str) -> Option<HashMap<String, String>> {
+    let content = fs::read_to_string(locales_dir().join(format!("{language}.txt"))).ok()?;
+    Some(parse_texts(&content))
+}
+
+// `key = text` lines, `#` starts a comment line
+fn parse_texts(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, text)| (key.trim().to_string(), text.trim().to_string()))
+        .collect()
+}
+
+pub fn locale_plugin(app: &mut App) {
+    app
+        .add_systems(Startup, replace_default_font)
+        .add_systems(Update, (switch_language, update_localized_texts).chain());
+}
+
+// the default font of every text style
+fn replace_default_font(mut fonts: ResMut<Assets<Font>>) {
+    match Font::try_from_bytes(FONT.to_vec()) {
+        Ok(font) => fonts.insert(Handle::<Font>::default(), font),
+        Err(error) => println!("Failed to load the font: {error}")
+    }
+}
+
+fn switch_language(settings: Res<Settings>, mut locale: ResMut<Locale>) {
+    if settings.is_changed() && settings.language != locale.language {
+        *locale = Locale::load(&settings.language);
+    }
+}
+
+fn update_localized_texts(locale: Res<Locale>, mut texts: Query<(&mut Text, Ref<LocalizedText>)>) {
+    for (mut text, localized_text) in &mut texts {
+        if locale.is_changed() || localized_text.is_added() {
+            text.sections[0].value = locale.get(localized_text.0);
+        }
+    }
+}
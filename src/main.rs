@@ -4,28 +4,78 @@ mod hex;
 mod ui;
 mod game;
 mod menu;
+mod settings;
+mod locale;
+mod audio;
+
+use std::time::Duration;
 
 use bevy::prelude::*;
 use bevy::render::camera::ScalingMode;
 use bevy::window::PrimaryWindow;
 
 use crate::ui::ui_plugin;
-use crate::game::game_plugin;
+use crate::game::{game_plugin, run_server, AbsentSeatPolicy, LobbySettings, ServerAddress, SUPPORTED_PLAYER_COUNTS};
 use crate::menu::menu_plugin;
+use crate::settings::{settings_plugin, Settings};
+use crate::locale::{locale_plugin, Locale};
+use crate::audio::audio_plugin;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7777";
+// listening on all interfaces lets other machines in the LAN join
+const DEFAULT_SERVER_ADDRESS: &str = "0.0.0.0:7777";
+const DEFAULT_SEAT_HOLD_SECONDS: u64 = 120;
 
+// gemblo --server [address] [player count] [seat hold seconds] [pause|pass|ai] runs a headless server
+// gemblo --connect [address] joins a game on the server
+// gemblo --spectate [address] watches a game on the server
 fn main() {
-    App::new()
+    let mut args = std::env::args().skip(1);
+    let mut server_address = None;
+    match args.next().as_deref() {
+        Some("--server") => {
+            let address = args.next().unwrap_or(DEFAULT_SERVER_ADDRESS.to_string());
+            let player_count = match args.next().map(|count| count.parse::<usize>()) {
+                None => 2,
+                Some(Ok(count)) if SUPPORTED_PLAYER_COUNTS.contains(&count) => count,
+                _ => {
+                    eprintln!("The player count must be one of {SUPPORTED_PLAYER_COUNTS:?}");
+                    return;
+                }
+            };
+            let seat_hold = args.next().and_then(|seconds| seconds.parse().ok()).unwrap_or(DEFAULT_SEAT_HOLD_SECONDS);
+            let absent_seat_policy = args.next().and_then(|policy| AbsentSeatPolicy::parse(&policy)).unwrap_or(AbsentSeatPolicy::Pause);
+            let settings = LobbySettings { player_count, seat_hold: Duration::from_secs(seat_hold), absent_seat_policy };
+            if let Err(error) = run_server(&address, settings) {
+                eprintln!("Server error: {error}");
+            }
+            return;
+        }
+        Some(mode @ ("--connect" | "--spectate")) => server_address = Some(ServerAddress {
+            address: args.next().unwrap_or(DEFAULT_ADDRESS.to_string()),
+            spectate: mode == "--spectate",
+        }),
+        _ => ()
+    }
+
+    let settings = Settings::load();
+    let locale = Locale::load(&settings.language);
+    let mut app = App::new();
+    if let Some(address) = server_address {
+        app.insert_resource(address);
+    }
+
+    app
         .add_plugins(DefaultPlugins
             .set(WindowPlugin {
-            primary_window: Some(Window {
-                position: WindowPosition::At(IVec2 { x: 0, y: 0 }),
-                ..default()
-            }),
+            primary_window: Some(settings.window()),
             ..default()
         }))
+        .insert_resource(settings)
+        .insert_resource(locale)
         .init_state::<GameState>()
         .insert_resource(CursorWorldCoords { ..default() })
-        .add_plugins((menu_plugin, ui_plugin, game_plugin))
+        .add_plugins((settings_plugin, locale_plugin, audio_plugin, menu_plugin, ui_plugin, game_plugin))
         .add_systems(Startup, setup)
         .add_systems(Update, world_cursor_system)
         .run();
@@ -50,12 +100,18 @@ fn setup(mut commands: Commands) {
 
 fn world_cursor_system(
     mut world_cursor: ResMut<CursorWorldCoords>,
+    touches: Res<Touches>,
     q_window: Query<&Window, With<PrimaryWindow>>,
     q_camera: Query<(&Camera, &GlobalTransform)>,
 ) {
     let (camera, camera_transform) = q_camera.single();
     let window = q_window.single();
-    if let Some(world_position) = window.cursor_position()
+    // the earliest finger drives the cursor, including on the frame it is lifted
+    let touch_position = touches.iter()
+        .chain(touches.iter_just_released())
+        .min_by_key(|touch| touch.id())
+        .map(|touch| touch.position());
+    if let Some(world_position) = touch_position.or_else(|| window.cursor_position())
         .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
         .map(|ray| ray.origin.truncate())
     {
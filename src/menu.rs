@@ -1,22 +1,186 @@
-use bevy::{app::AppExit, prelude::*};
+use std::net::SocketAddr;
+
+use bevy::{app::AppExit, ecs::system::SystemParam, input::mouse::MouseWheel, prelude::*, window::ReceivedCharacter};
 
 use crate::despawn_screen;
+use crate::locale::{Locale, LocalizedText};
+use crate::settings::{Settings, WINDOW_SIZES};
 
 use super::GameState;
-use super::game::Game;
+use super::game::{
+    discover_lan_games, start_discovery, stop_discovery, Action, Binding, Bindings, LanDiscovery, LanGame, Match,
+    MatchFormat, PlayerProfile, ServerAddress, BINDING_SLOTS, PALETTE_SIZE,
+};
 
+// State used for the current menu screen
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
+enum MenuState {
+    Main,
+    MatchSetup,
+    PlayerSetup,
+    LanGames,
+    Settings,
+    Controls,
+    #[default]
+    Disabled,
+}
 
 // Tag component used to tag entities added on the main menu screen
 #[derive(Component)]
 struct OnMainMenuScreen;
 
+// Tag component used to tag entities added on the match setup screen
+#[derive(Component)]
+struct OnMatchSetupScreen;
+
+// Label of a button on the match setup screen showing the current value
+#[derive(Component)]
+struct MatchSetupText(MenuButtonAction);
+
+const MATCH_PLAYER_COUNTS: [usize; 4] = [2, 3, 4, 6];
+const MATCH_GAME_COUNTS: [usize; 4] = [3, 5, 7, 1];
+const LEAGUE_SIZES: [usize; 4] = [3, 4, 5, 6];
+
+const MAX_NAME_LENGTH: usize = 16;
+const MAX_SEATS: usize = 6;
+
+// Tag component used to tag entities added on the player setup screen
+#[derive(Component)]
+struct OnPlayerSetupScreen;
+
+#[derive(Component)]
+struct PlayerNameText(usize);
+
+#[derive(Component)]
+struct PlayerColorSwatch(usize);
+
+// Tag component used to tag entities added on the settings screen
+#[derive(Component)]
+struct OnSettingsScreen;
+
+// Label of a button showing the current value of a setting
+#[derive(Component)]
+struct SettingsText(MenuButtonAction);
+
+const VOLUME_STEPS: f32 = 10.0;
+
+// Tag component used to tag entities added on the controls screen
+#[derive(Component)]
+struct OnControlsScreen;
+
+// Label of the button of one binding slot of an action
+#[derive(Component, Clone, Copy)]
+struct BindingText(Action, usize);
+
+// binding slot waiting for the next input
+#[derive(Resource, Default)]
+struct BindingCapture(Option<(Action, usize)>);
+
+// fixed key pressed for the slot waiting for an input, it is not bound
+#[derive(Resource, Default)]
+struct RejectedBinding(Option<Binding>);
+
+#[derive(Component)]
+struct RejectedBindingText;
+
+// choices on the match and player setup screens, kept while the app runs
+#[derive(Resource)]
+struct MatchSetup {
+    player_count: usize,
+    games: usize,
+    league_size: usize,
+    // match started from the player setup screen
+    format: MatchFormat,
+    seats: usize,
+    // names and colors of all seats, the first `seats` take part,
+    // empty names show the default one of the language
+    players: Vec<PlayerProfile>,
+    // seat whose name is typed in
+    editing: Option<usize>,
+}
+
+impl Default for MatchSetup {
+    fn default() -> Self {
+        Self {
+            player_count: MATCH_PLAYER_COUNTS[0],
+            games: MATCH_GAME_COUNTS[0],
+            league_size: LEAGUE_SIZES[1],
+            format: MatchFormat::Open,
+            seats: MATCH_PLAYER_COUNTS[0],
+            players: (0..MAX_SEATS).map(|color| PlayerProfile { name: String::new(), color }).collect(),
+            editing: None,
+        }
+    }
+}
+
+impl MatchSetup {
+    fn open_player_setup(&mut self, format: MatchFormat, seats: usize, menu_state: &mut NextState<MenuState>) {
+        self.format = format;
+        self.seats = seats;
+        self.editing = None;
+        // colors picked with fewer seats may repeat among the seats taking part now
+        for seat in 1..seats {
+            if self.players[..seat].iter().any(|other| other.color == self.players[seat].color) {
+                self.players[seat].color = self.next_free_color(seat);
+            }
+        }
+        menu_state.set(MenuState::PlayerSetup);
+    }
+
+    // palette color after the current one of the seat that no other seat in the match has
+    fn next_free_color(&self, seat: usize) -> usize {
+        let current = self.players[seat].color;
+        (1..=PALETTE_SIZE)
+            .map(|step| (current + step) % PALETTE_SIZE)
+            .find(|&color| (0..self.seats).all(|other| other == seat || self.players[other].color != color))
+            .unwrap_or(current)
+    }
+}
+
+// Tag component used to tag entities added on the LAN games screen
+#[derive(Component)]
+struct OnLanGamesScreen;
+
+// Node holding one button per discovered game
+#[derive(Component)]
+struct LanGamesList;
+
 pub fn menu_plugin(app: &mut App) {
     app
-    .add_systems(OnEnter(GameState::Menu), main_menu_setup)
-    .add_systems(OnExit(GameState::Menu), despawn_screen::<OnMainMenuScreen>)
+    .init_state::<MenuState>()
+    .init_resource::<MatchSetup>()
+    .init_resource::<BindingCapture>()
+    .init_resource::<RejectedBinding>()
+    .add_systems(OnEnter(GameState::Menu), menu_setup)
+    .add_systems(OnExit(GameState::Menu), menu_cleanup)
+    .add_systems(OnEnter(MenuState::Main), main_menu_setup)
+    .add_systems(OnExit(MenuState::Main), despawn_screen::<OnMainMenuScreen>)
+    .add_systems(OnEnter(MenuState::MatchSetup), match_setup_screen)
+    .add_systems(OnExit(MenuState::MatchSetup), despawn_screen::<OnMatchSetupScreen>)
+    .add_systems(Update, update_match_setup_texts.run_if(in_state(MenuState::MatchSetup)))
+    .add_systems(OnEnter(MenuState::PlayerSetup), player_setup_screen)
+    .add_systems(OnExit(MenuState::PlayerSetup), despawn_screen::<OnPlayerSetupScreen>)
+    .add_systems(
+        Update,
+        (type_player_name, update_player_setup).chain().run_if(in_state(MenuState::PlayerSetup)),
+    )
+    .add_systems(OnEnter(MenuState::LanGames), (lan_games_setup, start_discovery))
+    .add_systems(OnExit(MenuState::LanGames), (despawn_screen::<OnLanGamesScreen>, stop_discovery))
+    .add_systems(
+        Update,
+        (discover_lan_games, update_lan_games_list).chain().run_if(in_state(MenuState::LanGames)),
+    )
+    .add_systems(OnEnter(MenuState::Settings), settings_screen)
+    .add_systems(OnExit(MenuState::Settings), despawn_screen::<OnSettingsScreen>)
+    .add_systems(OnEnter(MenuState::Controls), controls_screen)
+    .add_systems(OnExit(MenuState::Controls), (despawn_screen::<OnControlsScreen>, stop_binding_capture))
     .add_systems(
         Update,
-        (menu_action, button_system).run_if(in_state(GameState::Menu)),
+        (capture_binding.before(menu_action), update_binding_texts, update_rejected_binding_text).run_if(in_state(MenuState::Controls)),
+    )
+    .add_systems(
+        Update,
+        (menu_action, menu_gamepad_navigation, button_system, update_settings_texts).run_if(in_state(GameState::Menu)),
     );
 
 
@@ -32,16 +196,56 @@ const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 #[derive(Component)]
 struct SelectedOption;
 
-// All actions that can be triggered from a button click
-#[derive(Component)]
+// All actions that can be triggered from a button click, in the order they are displayed
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum MenuButtonAction {
     Players2,
     Players3,
     Players4,
     Players6,
+    MatchSetup,
+    CycleMatchPlayers,
+    CycleMatchGames,
+    StartMatch,
+    CycleLeagueSize,
+    StartLeague,
+    EditName(usize),
+    CycleColor(usize),
+    CycleLanguage,
+    CyclePalette,
+    TogglePatterns,
+    StartGame,
+    JoinLanGame,
+    JoinGame(SocketAddr),
+    WatchGame(SocketAddr),
+    OpenSettings,
+    ToggleFullscreen,
+    CycleWindowSize,
+    CenterWindow,
+    CycleVolume,
+    CycleEffectsVolume,
+    CycleUiVolume,
+    ToggleMute,
+    CycleAnimationSpeed,
+    ToggleCorners,
+    ToggleTurnView,
+    ToggleFocusTray,
+    OpenControls,
+    Rebind(Action, usize),
+    ResetControls,
+    BackToSettings,
+    BackToMainMenu,
     Quit,
 }
 
+fn menu_setup(mut menu_state: ResMut<NextState<MenuState>>) {
+    menu_state.set(MenuState::Main);
+}
+
+fn menu_cleanup(mut menu_state: ResMut<NextState<MenuState>>) {
+    menu_state.set(MenuState::Disabled);
+}
+
 // This system handles changing all buttons color based on mouse interaction
 fn button_system(
     mut interaction_query: Query<
@@ -59,7 +263,7 @@ fn button_system(
     }
 }
 
-fn main_menu_setup(mut commands: Commands) {
+fn main_menu_setup(locale: Res<Locale>, mut commands: Commands) {
     // Common style for all buttons on the screen
     let button_style = Style {
         width: Val::Px(250.0),
@@ -102,9 +306,9 @@ fn main_menu_setup(mut commands: Commands) {
                 })
                 .with_children(|parent| {
                     // Display the game name
-                    parent.spawn(
+                    parent.spawn((
                         TextBundle::from_section(
-                            "Gemblo",
+                            "",
                             TextStyle {
                                 font_size: 80.0,
                                 color: TEXT_COLOR,
@@ -115,7 +319,8 @@ fn main_menu_setup(mut commands: Commands) {
                             margin: UiRect::all(Val::Px(50.0)),
                             ..default()
                         }),
-                    );
+                        LocalizedText("menu.title"),
+                    ));
 
                     // Display three buttons for each player count
                     parent
@@ -129,7 +334,7 @@ fn main_menu_setup(mut commands: Commands) {
                         ))
                         .with_children(|parent| {
                             parent.spawn(TextBundle::from_section(
-                                "2 Players",
+                                locale.plural("menu.players", 2, &[]),
                                 button_text_style.clone(),
                             ));
                         });
@@ -144,7 +349,7 @@ fn main_menu_setup(mut commands: Commands) {
                         ))
                         .with_children(|parent| {
                             parent.spawn(TextBundle::from_section(
-                                "3 Players",
+                                locale.plural("menu.players", 3, &[]),
                                 button_text_style.clone(),
                             ));
                         });
@@ -159,7 +364,7 @@ fn main_menu_setup(mut commands: Commands) {
                         ))
                         .with_children(|parent| {
                             parent.spawn(TextBundle::from_section(
-                                "4 Players",
+                                locale.plural("menu.players", 4, &[]),
                                 button_text_style.clone(),
                             ));
                         });
@@ -175,11 +380,59 @@ fn main_menu_setup(mut commands: Commands) {
                         ))
                         .with_children(|parent| {
                             parent.spawn(TextBundle::from_section(
-                                "6 Players",
+                                locale.plural("menu.players", 6, &[]),
                                 button_text_style.clone(),
                             ));
                         });
 
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::MatchSetup,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section("", button_text_style.clone()),
+                                LocalizedText("menu.match_league"),
+                            ));
+                        });
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::JoinLanGame,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section("", button_text_style.clone()),
+                                LocalizedText("menu.join_lan"),
+                            ));
+                        });
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::OpenSettings,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section("", button_text_style.clone()),
+                                LocalizedText("menu.settings"),
+                            ));
+                        });
+
                     parent
                         .spawn((
                             ButtonBundle {
@@ -190,45 +443,1105 @@ fn main_menu_setup(mut commands: Commands) {
                             MenuButtonAction::Quit,
                         ))
                         .with_children(|parent| {
-                            parent.spawn(TextBundle::from_section("Quit", button_text_style));
+                            parent.spawn((TextBundle::from_section("", button_text_style), LocalizedText("menu.quit")));
+                        });
+                });
+        });
+}
+
+fn match_setup_screen(mut commands: Commands) {
+    let button_style = Style {
+        width: Val::Px(400.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font_size: 35.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnMatchSetupScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::CRIMSON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 80.0,
+                                color: TEXT_COLOR,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(30.0)),
+                            ..default()
+                        }),
+                        LocalizedText("match_setup.title"),
+                    ));
+
+                    // texts of the cycling buttons are filled by update_match_setup_texts
+                    for (action, label) in [
+                        (MenuButtonAction::CycleMatchPlayers, ""),
+                        (MenuButtonAction::CycleMatchGames, ""),
+                        (MenuButtonAction::StartMatch, "match_setup.start_match"),
+                        (MenuButtonAction::CycleLeagueSize, ""),
+                        (MenuButtonAction::StartLeague, "match_setup.start_league"),
+                        (MenuButtonAction::BackToMainMenu, "menu.back"),
+                    ] {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: button_style.clone(),
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                },
+                                action,
+                            ))
+                            .with_children(|parent| {
+                                let mut text = parent.spawn((
+                                    TextBundle::from_section("", button_text_style.clone()),
+                                    MatchSetupText(action),
+                                ));
+                                if !label.is_empty() {
+                                    text.insert(LocalizedText(label));
+                                }
+                            });
+                    }
+                });
+        });
+}
+
+fn update_match_setup_texts(
+    match_setup: Res<MatchSetup>,
+    locale: Res<Locale>,
+    mut texts: Query<(&mut Text, Ref<MatchSetupText>)>,
+) {
+    for (mut text, setup_text) in &mut texts {
+        if !match_setup.is_changed() && !locale.is_changed() && !setup_text.is_added() {
+            continue;
+        }
+        let (key, count) = match setup_text.0 {
+            MenuButtonAction::CycleMatchPlayers => ("match_setup.match_players", match_setup.player_count),
+            MenuButtonAction::CycleMatchGames => ("match_setup.games", match_setup.games),
+            MenuButtonAction::CycleLeagueSize => ("match_setup.league_players", match_setup.league_size),
+            _ => continue
+        };
+        text.sections[0].value = locale.format(key, &[("count", &count)]);
+    }
+}
+
+// one step louder, silent after the loudest
+fn next_volume(volume: f32) -> f32 {
+    let step = (volume * VOLUME_STEPS).round() + 1.0;
+    if step > VOLUME_STEPS { 0.0 } else { step / VOLUME_STEPS }
+}
+
+// value after `current` in `values`, wrapping around
+fn cycle<T: Copy + PartialEq>(values: &[T], current: T) -> T {
+    let index = values.iter().position(|&value| value == current).unwrap_or(0);
+    values[(index + 1) % values.len()]
+}
+
+fn player_setup_screen(match_setup: Res<MatchSetup>, mut commands: Commands) {
+    let button_text_style = TextStyle {
+        font_size: 35.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnPlayerSetupScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::CRIMSON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 80.0,
+                                color: TEXT_COLOR,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(30.0)),
+                            ..default()
+                        }),
+                        LocalizedText("player_setup.title"),
+                    ));
+
+                    // click a name to type a new one, click the color to pick the next free one
+                    for seat in 0..match_setup.seats {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::Row,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                width: Val::Px(400.0),
+                                                height: Val::Px(55.0),
+                                                margin: UiRect::all(Val::Px(5.0)),
+                                                justify_content: JustifyContent::Center,
+                                                align_items: AlignItems::Center,
+                                                ..default()
+                                            },
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        MenuButtonAction::EditName(seat),
+                                    ))
+                                    .with_children(|parent| {
+                                        parent.spawn((
+                                            TextBundle::from_section("", button_text_style.clone()),
+                                            PlayerNameText(seat),
+                                        ));
+                                    });
+
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                width: Val::Px(100.0),
+                                                height: Val::Px(55.0),
+                                                margin: UiRect::all(Val::Px(5.0)),
+                                                justify_content: JustifyContent::Center,
+                                                align_items: AlignItems::Center,
+                                                ..default()
+                                            },
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        MenuButtonAction::CycleColor(seat),
+                                    ))
+                                    .with_children(|parent| {
+                                        // the button itself changes color on hover
+                                        parent.spawn((
+                                            NodeBundle {
+                                                style: Style {
+                                                    width: Val::Px(70.0),
+                                                    height: Val::Px(35.0),
+                                                    ..default()
+                                                },
+                                                ..default()
+                                            },
+                                            PlayerColorSwatch(seat),
+                                        ));
+                                    });
+                            });
+                    }
+
+                    // texts are filled by update_settings_texts
+                    for action in [MenuButtonAction::CyclePalette, MenuButtonAction::TogglePatterns] {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style { width: Val::Px(400.0), ..button_style.clone() },
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                },
+                                action,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    TextBundle::from_section("", button_text_style.clone()),
+                                    SettingsText(action),
+                                ));
+                            });
+                    }
+
+                    for (action, label) in [(MenuButtonAction::StartGame, "player_setup.start"), (MenuButtonAction::BackToMainMenu, "menu.back")] {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: button_style.clone(),
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                },
+                                action,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((TextBundle::from_section("", button_text_style.clone()), LocalizedText(label)));
+                            });
+                    }
+                });
+        });
+}
+
+fn type_player_name(
+    mut ev_characters: EventReader<ReceivedCharacter>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut match_setup: ResMut<MatchSetup>,
+) {
+    let Some(seat) = match_setup.editing else {
+        ev_characters.clear();
+        return;
+    };
+
+    if keys.any_just_pressed([KeyCode::Enter, KeyCode::Escape]) {
+        match_setup.editing = None;
+        ev_characters.clear();
+        return;
+    }
+    if keys.just_pressed(KeyCode::Backspace) {
+        match_setup.players[seat].name.pop();
+    }
+
+    for ev in ev_characters.read() {
+        let name = &mut match_setup.players[seat].name;
+        for character in ev.char.chars().filter(|character| !character.is_control()) {
+            if name.chars().count() < MAX_NAME_LENGTH {
+                name.push(character);
+            }
+        }
+    }
+}
+
+fn update_player_setup(
+    match_setup: Res<MatchSetup>,
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    mut names: Query<(&mut Text, Ref<PlayerNameText>)>,
+    mut swatches: Query<(&mut BackgroundColor, Ref<PlayerColorSwatch>)>,
+) {
+    for (mut text, name_text) in &mut names {
+        if match_setup.is_changed() || locale.is_changed() || name_text.is_added() {
+            let seat = name_text.0;
+            let name = &match_setup.players[seat].name;
+            // the default name is shown grayed until one is typed
+            text.sections[0].value = if match_setup.editing == Some(seat) {
+                format!("{name}_")
+            } else if name.trim().is_empty() {
+                locale.default_player_name(seat)
+            } else {
+                name.clone()
+            };
+            text.sections[0].style.color = if name.trim().is_empty() { Color::GRAY } else { TEXT_COLOR };
+        }
+    }
+    for (mut color, swatch) in &mut swatches {
+        if match_setup.is_changed() || settings.is_changed() || swatch.is_added() {
+            *color = match_setup.players[swatch.0].display_color(settings.palette).into();
+        }
+    }
+}
+
+fn settings_screen(mut commands: Commands) {
+    let button_style = Style {
+        width: Val::Px(500.0),
+        height: Val::Px(50.0),
+        margin: UiRect::all(Val::Px(4.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font_size: 35.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnSettingsScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::CRIMSON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 60.0,
+                                color: TEXT_COLOR,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        }),
+                        LocalizedText("settings.title"),
+                    ));
+
+                    // two columns, texts of the setting buttons are filled by update_settings_texts
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                display: Display::Grid,
+                                grid_template_columns: RepeatedGridTrack::auto(2),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (action, label) in [
+                                (MenuButtonAction::CycleLanguage, ""),
+                                (MenuButtonAction::CyclePalette, ""),
+                                (MenuButtonAction::TogglePatterns, ""),
+                                (MenuButtonAction::ToggleFullscreen, ""),
+                                (MenuButtonAction::CycleWindowSize, ""),
+                                (MenuButtonAction::CenterWindow, ""),
+                                (MenuButtonAction::CycleVolume, ""),
+                                (MenuButtonAction::CycleEffectsVolume, ""),
+                                (MenuButtonAction::CycleUiVolume, ""),
+                                (MenuButtonAction::ToggleMute, ""),
+                                (MenuButtonAction::CycleAnimationSpeed, ""),
+                                (MenuButtonAction::ToggleCorners, ""),
+                                (MenuButtonAction::ToggleTurnView, ""),
+                                (MenuButtonAction::ToggleFocusTray, ""),
+                            ] {
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: button_style.clone(),
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        action,
+                                    ))
+                                    .with_children(|parent| {
+                                        let mut text = parent.spawn((
+                                            TextBundle::from_section("", button_text_style.clone()),
+                                            SettingsText(action),
+                                        ));
+                                        if !label.is_empty() {
+                                            text.insert(LocalizedText(label));
+                                        }
+                                    });
+                            }
+                        });
+
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Row,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (action, label) in [
+                                (MenuButtonAction::OpenControls, "settings.controls"),
+                                (MenuButtonAction::BackToMainMenu, "menu.back"),
+                            ] {
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: button_style.clone(),
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        action,
+                                    ))
+                                    .with_children(|parent| {
+                                        parent.spawn((TextBundle::from_section("", button_text_style.clone()), LocalizedText(label)));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+fn update_settings_texts(settings: Res<Settings>, locale: Res<Locale>, mut texts: Query<(&mut Text, Ref<SettingsText>)>) {
+    for (mut text, settings_text) in &mut texts {
+        if !settings.is_changed() && !locale.is_changed() && !settings_text.is_added() {
+            continue;
+        }
+        let state = |value| locale.on_off(value);
+        text.sections[0].value = match settings_text.0 {
+            MenuButtonAction::CycleLanguage => locale.format("settings.language", &[("language", &locale.language_name())]),
+            MenuButtonAction::CyclePalette => locale.format("settings.colors", &[("palette", &locale.get(settings.palette.locale_key()))]),
+            MenuButtonAction::TogglePatterns => locale.format("settings.patterns", &[("state", &state(settings.patterns))]),
+            MenuButtonAction::ToggleFullscreen => locale.format("settings.fullscreen", &[("state", &state(settings.fullscreen))]),
+            MenuButtonAction::CycleWindowSize => locale.format(
+                "settings.window",
+                &[("width", &settings.window_size.x), ("height", &settings.window_size.y)],
+            ),
+            // a click centers the window again
+            MenuButtonAction::CenterWindow => match settings.window_position {
+                Some(position) => locale.format("settings.position", &[("x", &position.x), ("y", &position.y)]),
+                None => locale.get("settings.position_centered"),
+            },
+            MenuButtonAction::CycleVolume => locale.format("settings.volume", &[("percent", &(settings.volume * 100.0).round())]),
+            MenuButtonAction::CycleEffectsVolume => {
+                locale.format("settings.effects_volume", &[("percent", &(settings.effects_volume * 100.0).round())])
+            }
+            MenuButtonAction::CycleUiVolume => locale.format("settings.ui_volume", &[("percent", &(settings.ui_volume * 100.0).round())]),
+            // on while the sound is not muted
+            MenuButtonAction::ToggleMute => locale.format("settings.sound", &[("state", &state(!settings.muted))]),
+            MenuButtonAction::CycleAnimationSpeed => {
+                locale.format("settings.animations", &[("speed", &locale.get(settings.animation_speed.locale_key()))])
+            }
+            MenuButtonAction::ToggleCorners => locale.format("settings.corners", &[("state", &state(settings.show_corners))]),
+            MenuButtonAction::ToggleTurnView => locale.format("settings.turn_view", &[("state", &state(settings.rotate_view))]),
+            MenuButtonAction::ToggleFocusTray => locale.format("settings.focus", &[("state", &state(settings.focus_tray))]),
+            _ => continue
+        };
+    }
+}
+
+fn controls_screen(mut commands: Commands) {
+    let button_text_style = TextStyle {
+        font_size: 30.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+    let button_style = Style {
+        width: Val::Px(400.0),
+        height: Val::Px(50.0),
+        margin: UiRect::all(Val::Px(4.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnControlsScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::CRIMSON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 60.0,
+                                color: TEXT_COLOR,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        }),
+                        LocalizedText("controls.title"),
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section("", TextStyle { font_size: 25.0, ..button_text_style.clone() }),
+                        LocalizedText("controls.help"),
+                    ));
+                    parent.spawn((
+                        TextBundle::from_section("", TextStyle { font_size: 25.0, ..button_text_style.clone() }),
+                        RejectedBindingText,
+                    ));
+
+                    // one row per action, the slot texts are filled by update_binding_texts
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                display: Display::Grid,
+                                grid_template_columns: RepeatedGridTrack::auto(1 + BINDING_SLOTS as u16),
+                                column_gap: Val::Px(10.0),
+                                align_items: AlignItems::Center,
+                                margin: UiRect::vertical(Val::Px(10.0)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for action in Action::ALL {
+                                parent.spawn((
+                                    TextBundle::from_section("", button_text_style.clone()),
+                                    LocalizedText(action.locale_key()),
+                                ));
+                                for slot in 0..BINDING_SLOTS {
+                                    parent
+                                        .spawn((
+                                            ButtonBundle {
+                                                style: Style {
+                                                    width: Val::Px(220.0),
+                                                    height: Val::Px(45.0),
+                                                    margin: UiRect::all(Val::Px(3.0)),
+                                                    justify_content: JustifyContent::Center,
+                                                    align_items: AlignItems::Center,
+                                                    ..default()
+                                                },
+                                                background_color: NORMAL_BUTTON.into(),
+                                                ..default()
+                                            },
+                                            MenuButtonAction::Rebind(action, slot),
+                                        ))
+                                        .with_children(|parent| {
+                                            parent.spawn((
+                                                TextBundle::from_section("", button_text_style.clone()),
+                                                BindingText(action, slot),
+                                            ));
+                                        });
+                                }
+                            }
+                        });
+
+                    for (action, label) in [
+                        (MenuButtonAction::ResetControls, "controls.reset"),
+                        (MenuButtonAction::BackToSettings, "menu.back"),
+                    ] {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: button_style.clone(),
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                },
+                                action,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn((TextBundle::from_section("", button_text_style.clone()), LocalizedText(label)));
+                            });
+                    }
+                });
+        });
+}
+
+// the slot waiting for an input takes the first key, mouse button or wheel step,
+// one bound to another slot swaps with it and a fixed key is turned down
+fn capture_binding(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut scroll_evr: EventReader<MouseWheel>,
+    mut binding_capture: ResMut<BindingCapture>,
+    mut rejected_binding: ResMut<RejectedBinding>,
+    mut settings: ResMut<Settings>,
+) {
+    let scroll: f32 = scroll_evr.read().map(|ev| ev.y).sum();
+    let Some((action, slot)) = binding_capture.0 else {
+        return;
+    };
+
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let binding = if keys.just_pressed(KeyCode::Backspace) {
+        None
+    } else if let Some(binding) = keys.get_just_pressed().find_map(|&key| Binding::from_key(key, shift)) {
+        Some(binding)
+    } else if let Some(&button) = mouse_buttons.get_just_pressed().next() {
+        Some(Binding::Mouse(button))
+    } else if scroll != 0.0 {
+        Some(if scroll > 0.0 { Binding::WheelUp } else { Binding::WheelDown })
+    } else {
+        return;
+    };
+
+    if let Some(fixed) = binding.filter(|binding| binding.is_fixed()) {
+        rejected_binding.0 = Some(fixed);
+        return;
+    }
+
+    settings.bindings.assign(action, slot, binding);
+    binding_capture.0 = None;
+    rejected_binding.0 = None;
+}
+
+fn update_rejected_binding_text(
+    locale: Res<Locale>,
+    rejected_binding: Res<RejectedBinding>,
+    mut texts: Query<&mut Text, With<RejectedBindingText>>,
+) {
+    if !locale.is_changed() && !rejected_binding.is_changed() {
+        return;
+    }
+    for mut text in &mut texts {
+        text.sections[0].value = rejected_binding.0
+            .map_or(String::new(), |binding| locale.format("controls.fixed_key", &[("key", &binding.name())]));
+    }
+}
+
+fn update_binding_texts(
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    binding_capture: Res<BindingCapture>,
+    mut texts: Query<(&mut Text, Ref<BindingText>)>,
+) {
+    for (mut text, binding_text) in &mut texts {
+        if !settings.is_changed() && !locale.is_changed() && !binding_capture.is_changed() && !binding_text.is_added() {
+            continue;
+        }
+        let BindingText(action, slot) = *binding_text;
+        text.sections[0].value = if binding_capture.0 == Some((action, slot)) {
+            "...".to_string()
+        } else {
+            settings.bindings.get(action)[slot].map_or("-".to_string(), |binding| binding_label(binding, &locale))
+        };
+    }
+}
+
+// key names stay as printed on the keyboard
+fn binding_label(binding: Binding, locale: &Locale) -> String {
+    match binding {
+        Binding::Mouse(MouseButton::Left) => locale.get("binding.left_click"),
+        Binding::Mouse(MouseButton::Right) => locale.get("binding.right_click"),
+        Binding::Mouse(MouseButton::Middle) => locale.get("binding.middle_click"),
+        Binding::Mouse(button) => locale.format("binding.mouse", &[("button", &format!("{button:?}"))]),
+        Binding::WheelUp => locale.get("binding.wheel_up"),
+        Binding::WheelDown => locale.get("binding.wheel_down"),
+        Binding::Key(_) | Binding::ShiftKey(_) => binding.name(),
+    }
+}
+
+fn stop_binding_capture(mut binding_capture: ResMut<BindingCapture>, mut rejected_binding: ResMut<RejectedBinding>) {
+    binding_capture.0 = None;
+    rejected_binding.0 = None;
+}
+
+fn lan_games_setup(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnLanGamesScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::CRIMSON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 80.0,
+                                color: TEXT_COLOR,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(50.0)),
+                            ..default()
+                        }),
+                        LocalizedText("lan.title"),
+                    ));
+
+                    // filled by update_lan_games_list
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        LanGamesList,
+                    ));
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(250.0),
+                                    height: Val::Px(65.0),
+                                    margin: UiRect::all(Val::Px(20.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::BackToMainMenu,
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font_size: 40.0,
+                                        color: TEXT_COLOR,
+                                        ..default()
+                                    },
+                                ),
+                                LocalizedText("menu.back"),
+                            ));
                         });
                 });
         });
 }
 
+// rebuilds the buttons only when a game appears, disappears or changes its seats
+// or the language changes, so hovered buttons are not replaced every frame
+fn update_lan_games_list(
+    discovery: Option<Res<LanDiscovery>>,
+    locale: Res<Locale>,
+    q_list: Query<Entity, With<LanGamesList>>,
+    mut shown_games: Local<Option<Vec<(SocketAddr, usize, usize, bool)>>>,
+    mut commands: Commands,
+) {
+    let full_game = discovery.as_ref().and_then(|discovery| discovery.full_game);
+    let games: Vec<LanGame> = discovery.map(|discovery| discovery.games.clone()).unwrap_or_default();
+    let summary: Vec<(SocketAddr, usize, usize, bool)> = games
+        .iter()
+        .map(|game| (game.address, game.player_count, game.free_seats, full_game == Some(game.address)))
+        .collect();
+    let Ok(list) = q_list.get_single() else {
+        *shown_games = None;
+        return;
+    };
+    if shown_games.as_ref() == Some(&summary) && !locale.is_changed() {
+        return;
+    }
+    *shown_games = Some(summary);
+
+    let button_text_style = TextStyle {
+        font_size: 30.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+
+    commands.entity(list).despawn_descendants().with_children(|parent| {
+        if games.is_empty() {
+            parent.spawn(
+                TextBundle::from_section(locale.get("lan.searching"), button_text_style.clone())
+                    .with_style(Style {
+                        margin: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    }),
+            );
+        }
+
+        for game in &games {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    min_width: Val::Px(600.0),
+                                    height: Val::Px(50.0),
+                                    margin: UiRect::all(Val::Px(10.0)),
+                                    padding: UiRect::horizontal(Val::Px(20.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::JoinGame(game.address),
+                        ))
+                        .with_children(|parent| {
+                            // a join turned away for having no free seat shows until the list is left
+                            let free = if full_game == Some(game.address) {
+                                locale.get("lan.full")
+                            } else {
+                                locale.plural("lan.free_seats", game.free_seats, &[])
+                            };
+                            let label = locale.format(
+                                "lan.game",
+                                &[
+                                    ("address", &game.address),
+                                    ("board", &locale.get(game.board_locale_key())),
+                                    ("players", &locale.plural("lan.players", game.player_count, &[])),
+                                    ("free", &free),
+                                ],
+                            );
+                            parent.spawn(TextBundle::from_section(label, button_text_style.clone()));
+                        });
+
+                    parent
+                        .spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(120.0),
+                                    height: Val::Px(50.0),
+                                    margin: UiRect::all(Val::Px(10.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            },
+                            MenuButtonAction::WatchGame(game.address),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn(TextBundle::from_section(locale.get("lan.watch"), button_text_style.clone()));
+                        });
+                });
+        }
+    });
+}
+
+// everything a menu button can change, pressed with the mouse or the gamepad
+#[derive(SystemParam)]
+struct MenuActionParams<'w, 's> {
+    app_exit_events: EventWriter<'w, AppExit>,
+    menu_state: ResMut<'w, NextState<MenuState>>,
+    game_state: ResMut<'w, NextState<GameState>>,
+    match_setup: ResMut<'w, MatchSetup>,
+    settings: ResMut<'w, Settings>,
+    locale: Res<'w, Locale>,
+    binding_capture: ResMut<'w, BindingCapture>,
+    commands: Commands<'w, 's>,
+}
+
 fn menu_action(
     interaction_query: Query<
         (&Interaction, &MenuButtonAction),
         (Changed<Interaction>, With<Button>),
     >,
-    mut app_exit_events: EventWriter<AppExit>,
-    mut game_state: ResMut<NextState<GameState>>,
-    mut commands: Commands,
+    mut params: MenuActionParams,
 ) {
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
-            match menu_button_action {
-                MenuButtonAction::Quit => {
-                    app_exit_events.send(AppExit);
-                }
-
-                MenuButtonAction::Players2 => {
-                    commands.insert_resource(Game::new(2));
-                    game_state.set(GameState::Game);
-                }
-                MenuButtonAction::Players3 => {
-                    commands.insert_resource(Game::new(3));
-                    game_state.set(GameState::Game);
-                }
-                MenuButtonAction::Players4 => {
-                    commands.insert_resource(Game::new(4));
-                    game_state.set(GameState::Game);
-                }
-                MenuButtonAction::Players6 => {
-                    commands.insert_resource(Game::new(6));
-                    game_state.set(GameState::Game);
-                }
+            run_menu_action(*menu_button_action, &mut params);
+        }
+    }
+}
+
+// D-pad moves the focus between buttons, South presses the focused one
+fn menu_gamepad_navigation(
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mut focused: Local<Option<MenuButtonAction>>,
+    mut menu_buttons: Query<(&MenuButtonAction, &mut BackgroundColor)>,
+    mut params: MenuActionParams,
+) {
+    let mut actions: Vec<MenuButtonAction> = menu_buttons.iter().map(|(action, _)| *action).collect();
+    actions.sort();
+    if actions.is_empty() {
+        return;
+    }
+
+    for gamepad in gamepads.iter() {
+        let just_pressed = |button_type| buttons.just_pressed(GamepadButton::new(gamepad, button_type));
+        let focused_index = focused.and_then(|action| actions.iter().position(|&a| a == action));
+
+        let next_index = if just_pressed(GamepadButtonType::DPadDown) {
+            Some(focused_index.map_or(0, |index| (index + 1) % actions.len()))
+        } else if just_pressed(GamepadButtonType::DPadUp) {
+            Some(focused_index.map_or(actions.len() - 1, |index| (index + actions.len() - 1) % actions.len()))
+        } else {
+            None
+        };
+
+        if let Some(index) = next_index {
+            *focused = Some(actions[index]);
+            for (action, mut color) in &mut menu_buttons {
+                *color = if *action == actions[index] { HOVERED_BUTTON.into() } else { NORMAL_BUTTON.into() };
             }
         }
+
+        if just_pressed(GamepadButtonType::South) {
+            if let Some(action) = *focused {
+                *focused = None;
+                run_menu_action(action, &mut params);
+                return;
+            }
+        }
+    }
+}
+
+fn run_menu_action(menu_button_action: MenuButtonAction, params: &mut MenuActionParams) {
+    let MenuActionParams {
+        app_exit_events, menu_state, game_state, match_setup, settings, locale, binding_capture, commands
+    } = params;
+    if !matches!(menu_button_action, MenuButtonAction::EditName(_)) {
+        match_setup.editing = None;
+    }
+
+    match menu_button_action {
+        MenuButtonAction::Quit => {
+            app_exit_events.send(AppExit);
+        }
+
+        MenuButtonAction::Players2 => match_setup.open_player_setup(MatchFormat::Open, 2, menu_state),
+        MenuButtonAction::Players3 => match_setup.open_player_setup(MatchFormat::Open, 3, menu_state),
+        MenuButtonAction::Players4 => match_setup.open_player_setup(MatchFormat::Open, 4, menu_state),
+        MenuButtonAction::Players6 => match_setup.open_player_setup(MatchFormat::Open, 6, menu_state),
+        MenuButtonAction::MatchSetup => menu_state.set(MenuState::MatchSetup),
+        MenuButtonAction::CycleMatchPlayers => match_setup.player_count = cycle(&MATCH_PLAYER_COUNTS, match_setup.player_count),
+        MenuButtonAction::CycleMatchGames => match_setup.games = cycle(&MATCH_GAME_COUNTS, match_setup.games),
+        MenuButtonAction::CycleLeagueSize => match_setup.league_size = cycle(&LEAGUE_SIZES, match_setup.league_size),
+        MenuButtonAction::StartMatch => {
+            let format = MatchFormat::Series { games: match_setup.games };
+            let seats = match_setup.player_count;
+            match_setup.open_player_setup(format, seats, menu_state);
+        }
+        // two player games between every pair of players
+        MenuButtonAction::StartLeague => {
+            let seats = match_setup.league_size;
+            match_setup.open_player_setup(MatchFormat::League, seats, menu_state);
+        }
+        MenuButtonAction::EditName(seat) => {
+            match_setup.editing = if match_setup.editing == Some(seat) { None } else { Some(seat) };
+        }
+        MenuButtonAction::CycleColor(seat) => {
+            match_setup.players[seat].color = match_setup.next_free_color(seat);
+        }
+        MenuButtonAction::CycleLanguage => settings.language = locale.next_language(),
+        MenuButtonAction::CyclePalette => settings.palette = settings.palette.next(),
+        MenuButtonAction::TogglePatterns => settings.patterns = !settings.patterns,
+        MenuButtonAction::StartGame => {
+            let players = match_setup.players[..match_setup.seats]
+                .iter()
+                .enumerate()
+                .map(|(seat, profile)| PlayerProfile {
+                    name: match profile.name.trim() {
+                        "" => locale.default_player_name(seat),
+                        name => name.to_string(),
+                    },
+                    color: profile.color,
+                })
+                .collect();
+            let new_match = Match::new(match_setup.format, players, settings.palette);
+            commands.insert_resource(settings.with_rule_defaults(new_match.first_game()));
+            commands.insert_resource(new_match);
+            game_state.set(GameState::Game);
+        }
+        MenuButtonAction::JoinLanGame => menu_state.set(MenuState::LanGames),
+        // the game starts once the server welcomes us
+        MenuButtonAction::JoinGame(address) => {
+            commands.remove_resource::<Match>();
+            commands.insert_resource(ServerAddress { address: address.to_string(), spectate: false });
+        }
+        MenuButtonAction::WatchGame(address) => {
+            commands.remove_resource::<Match>();
+            commands.insert_resource(ServerAddress { address: address.to_string(), spectate: true });
+        }
+        MenuButtonAction::OpenSettings => menu_state.set(MenuState::Settings),
+        MenuButtonAction::ToggleFullscreen => settings.fullscreen = !settings.fullscreen,
+        MenuButtonAction::CycleWindowSize => settings.window_size = cycle(&WINDOW_SIZES, settings.window_size),
+        MenuButtonAction::CenterWindow => settings.window_position = None,
+        MenuButtonAction::CycleVolume => settings.volume = next_volume(settings.volume),
+        MenuButtonAction::CycleEffectsVolume => settings.effects_volume = next_volume(settings.effects_volume),
+        MenuButtonAction::CycleUiVolume => settings.ui_volume = next_volume(settings.ui_volume),
+        MenuButtonAction::ToggleMute => settings.muted = !settings.muted,
+        MenuButtonAction::CycleAnimationSpeed => settings.animation_speed = settings.animation_speed.next(),
+        MenuButtonAction::ToggleCorners => settings.show_corners = !settings.show_corners,
+        MenuButtonAction::ToggleTurnView => settings.rotate_view = !settings.rotate_view,
+        MenuButtonAction::ToggleFocusTray => settings.focus_tray = !settings.focus_tray,
+        MenuButtonAction::OpenControls => menu_state.set(MenuState::Controls),
+        MenuButtonAction::Rebind(action, slot) => binding_capture.0 = Some((action, slot)),
+        MenuButtonAction::ResetControls => settings.bindings = Bindings::default(),
+        MenuButtonAction::BackToSettings => menu_state.set(MenuState::Settings),
+        MenuButtonAction::BackToMainMenu => menu_state.set(MenuState::Main),
     }
 }
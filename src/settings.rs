@@ -0,0 +1,327 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode, WindowMoved, WindowPosition, WindowResized, WindowResolution};
+
+use crate::game::{Action, AnimationSpeed, Bindings, ColorPalette, Game};
+use crate::locale::DEFAULT_LANGUAGE;
+
+const SETTINGS_FILE: &str = "settings.cfg";
+// window moves and resizes come every frame while dragging, the file is written once they stop
+const SAVE_DELAY: f32 = 0.5;
+
+pub const WINDOW_SIZES: [UVec2; 4] = [
+    UVec2 { x: 1280, y: 720 },
+    UVec2 { x: 1600, y: 900 },
+    UVec2 { x: 1920, y: 1080 },
+    UVec2 { x: 2560, y: 1440 },
+];
+
+// preferences changed in the settings screen, loaded at startup and saved when they change
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Settings {
+    // code of the language, the name of its file in assets/locales
+    pub language: String,
+    pub fullscreen: bool,
+    pub window_size: UVec2,
+    // last position of the window, centered when not set
+    pub window_position: Option<IVec2>,
+    // master volume from 0 to 1, the others scale the sounds of the game and of the interface
+    pub volume: f32,
+    pub effects_volume: f32,
+    pub ui_volume: f32,
+    pub muted: bool,
+    // how fast pieces move and turn, or not animated at all
+    pub animation_speed: AnimationSpeed,
+    pub palette: ColorPalette,
+    // symbol drawn over the hexes of each player
+    pub patterns: bool,
+    // options new games start with, rematches keep the ones of the previous game
+    pub show_corners: bool,
+    pub rotate_view: bool,
+    pub focus_tray: bool,
+    pub bindings: Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            language: DEFAULT_LANGUAGE.to_string(),
+            fullscreen: false,
+            window_size: WINDOW_SIZES[0],
+            window_position: None,
+            volume: 1.0,
+            effects_volume: 1.0,
+            ui_volume: 1.0,
+            muted: false,
+            animation_speed: AnimationSpeed::default(),
+            palette: ColorPalette::default(),
+            patterns: false,
+            show_corners: false,
+            rotate_view: false,
+            focus_tray: false,
+            bindings: Bindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        settings_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map_or_else(Self::default, |content| Self::parse(&content))
+    }
+
+    // `key = value` lines of the settings file, missing or broken values keep their defaults
+    fn parse(content: &str) -> Self {
+        let mut settings = Self::default();
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "language" if !value.is_empty() => settings.language = value.to_string(),
+                "fullscreen" => settings.fullscreen = value.parse().unwrap_or(settings.fullscreen),
+                "window_size" => settings.window_size = parse_pair(value).map_or(settings.window_size, UVec2::from),
+                "window_position" => settings.window_position = parse_pair(value).map(IVec2::from),
+                "volume" => settings.volume = parse_volume(value).unwrap_or(settings.volume),
+                "effects_volume" => settings.effects_volume = parse_volume(value).unwrap_or(settings.effects_volume),
+                "ui_volume" => settings.ui_volume = parse_volume(value).unwrap_or(settings.ui_volume),
+                "muted" => settings.muted = value.parse().unwrap_or(settings.muted),
+                "animation_speed" => settings.animation_speed = AnimationSpeed::ALL
+                    .into_iter()
+                    .find(|speed| speed.name() == value)
+                    .unwrap_or(settings.animation_speed),
+                "palette" => settings.palette = ColorPalette::ALL
+                    .into_iter()
+                    .find(|palette| palette.name() == value)
+                    .unwrap_or(settings.palette),
+                "patterns" => settings.patterns = value.parse().unwrap_or(settings.patterns),
+                "show_corners" => settings.show_corners = value.parse().unwrap_or(settings.show_corners),
+                "rotate_view" => settings.rotate_view = value.parse().unwrap_or(settings.rotate_view),
+                "focus_tray" => settings.focus_tray = value.parse().unwrap_or(settings.focus_tray),
+                key => if let Some(action) = Action::ALL.into_iter().find(|action| action.config_key() == key) {
+                    for (slot, binding) in Bindings::parse_slots(value).into_iter().enumerate() {
+                        settings.bindings.set(action, slot, binding);
+                    }
+                }
+            }
+        }
+        settings
+    }
+
+    fn save(&self) {
+        let Some(path) = settings_path() else {
+            println!("No config directory to save the settings to");
+            return;
+        };
+        let mut content = format!("language = {}\n", self.language);
+        content += &format!("fullscreen = {}\n", self.fullscreen);
+        content += &format!("window_size = {} {}\n", self.window_size.x, self.window_size.y);
+        if let Some(position) = self.window_position {
+            content += &format!("window_position = {} {}\n", position.x, position.y);
+        }
+        content += &format!("volume = {}\n", self.volume);
+        content += &format!("effects_volume = {}\n", self.effects_volume);
+        content += &format!("ui_volume = {}\n", self.ui_volume);
+        content += &format!("muted = {}\n", self.muted);
+        content += &format!("animation_speed = {}\n", self.animation_speed.name());
+        content += &format!("palette = {}\n", self.palette.name());
+        content += &format!("patterns = {}\n", self.patterns);
+        content += &format!("show_corners = {}\n", self.show_corners);
+        content += &format!("rotate_view = {}\n", self.rotate_view);
+        content += &format!("focus_tray = {}\n", self.focus_tray);
+        for action in Action::ALL {
+            content += &format!("{} = {}\n", action.config_key(), self.bindings.slots_to_string(action));
+        }
+
+        let saved = path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&path, content));
+        if let Err(error) = saved {
+            println!("Failed to save the settings to {}: {error}", path.display());
+        }
+    }
+
+    pub fn window(&self) -> Window {
+        Window {
+            mode: if self.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed },
+            resolution: WindowResolution::new(self.window_size.x as f32, self.window_size.y as f32),
+            position: self.window_position.map_or(WindowPosition::Centered(MonitorSelection::Primary), WindowPosition::At),
+            ..default()
+        }
+    }
+
+    // a game started from the menu or joined over the network
+    pub fn with_rule_defaults(&self, mut game: Game) -> Game {
+        game.show_corners = self.show_corners;
+        game.rotate_view = self.rotate_view;
+        game.focus_tray = self.focus_tray;
+        game
+    }
+}
+
+fn parse_volume(value: &str) -> Option<f32> {
+    value.parse::<f32>().ok().map(|volume| volume.clamp(0.0, 1.0))
+}
+
+// two whitespace separated numbers
+fn parse_pair<T: std::str::FromStr>(value: &str) -> Option<[T; 2]> {
+    let mut numbers = value.split_whitespace().map(|number| number.parse().ok());
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Some(first)), Some(Some(second)), None) => Some([first, second]),
+        _ => None
+    }
+}
+
+fn settings_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(SETTINGS_FILE))
+}
+
+// the usual per user config directory of each platform, the files of the game go in it
+pub fn config_dir() -> Option<PathBuf> {
+    let env_path = |name| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let config_dir = if cfg!(target_os = "windows") {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_path("XDG_CONFIG_HOME").or_else(|| env_path("HOME").map(|home| home.join(".config")))
+    };
+    config_dir.map(|dir| dir.join("gemblo"))
+}
+
+pub fn settings_plugin(app: &mut App) {
+    app
+        .init_resource::<Settings>()
+        .add_systems(Update, (track_window, apply_window_settings, apply_volume).chain())
+        .add_systems(Last, save_settings);
+}
+
+// moving or resizing the window by hand is remembered for the next start
+fn track_window(
+    mut ev_moved: EventReader<WindowMoved>,
+    mut ev_resized: EventReader<WindowResized>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut settings: ResMut<Settings>,
+) {
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+    // fullscreen covers the monitor, the windowed geometry stays as it was
+    if window.mode != WindowMode::Windowed {
+        ev_moved.clear();
+        ev_resized.clear();
+        return;
+    }
+
+    if let Some(moved) = ev_moved.read().last() {
+        let position = Some(moved.position);
+        if settings.window_position != position {
+            settings.window_position = position;
+        }
+    }
+    if let Some(resized) = ev_resized.read().last() {
+        let size = UVec2 { x: resized.width.round() as u32, y: resized.height.round() as u32 };
+        if settings.window_size != size {
+            settings.window_size = size;
+        }
+    }
+}
+
+fn apply_window_settings(settings: Res<Settings>, mut q_window: Query<&mut Window, With<PrimaryWindow>>) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+    let Ok(mut window) = q_window.get_single_mut() else {
+        return;
+    };
+
+    let target = settings.window();
+    if window.mode != target.mode {
+        window.mode = target.mode;
+    }
+    if target.mode == WindowMode::Windowed {
+        if window.resolution.width() != target.resolution.width() || window.resolution.height() != target.resolution.height() {
+            window.resolution.set(target.resolution.width(), target.resolution.height());
+        }
+        if window.position != target.position {
+            window.position = target.position;
+        }
+    }
+}
+
+// sounds already playing keep the volume they started with
+fn apply_volume(settings: Res<Settings>, mut global_volume: ResMut<GlobalVolume>) {
+    if settings.is_changed() {
+        global_volume.volume = Volume::new(if settings.muted { 0.0 } else { settings.volume });
+    }
+}
+
+fn save_settings(
+    settings: Res<Settings>,
+    time: Res<Time>,
+    ev_exit: EventReader<AppExit>,
+    mut unsaved_for: Local<Option<f32>>,
+) {
+    if settings.is_changed() && !settings.is_added() {
+        *unsaved_for = Some(0.0);
+    }
+    let Some(elapsed) = *unsaved_for else {
+        return;
+    };
+
+    if elapsed >= SAVE_DELAY || !ev_exit.is_empty() {
+        settings.save();
+        *unsaved_for = None;
+    } else {
+        *unsaved_for = Some(elapsed + time.delta_seconds());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_read_from_their_lines() {
+        let settings = Settings::parse(
+            "language = de\nfullscreen = true\nwindow_size = 1600 900\nwindow_position = -20 35\nvolume = 0.25\n\
+             effects_volume = 0.5\nui_volume = 0.75\nmuted = true\npalette = Tritanopia\npatterns = true\nshow_corners = true\nrotate_view = true\nfocus_tray = true\n",
+        );
+        assert_eq!(settings.language, "de");
+        assert!(settings.fullscreen);
+        assert_eq!(settings.window_size, UVec2 { x: 1600, y: 900 });
+        assert_eq!(settings.window_position, Some(IVec2 { x: -20, y: 35 }));
+        assert_eq!(settings.volume, 0.25);
+        assert_eq!(settings.effects_volume, 0.5);
+        assert_eq!(settings.ui_volume, 0.75);
+        assert!(settings.muted);
+        assert_eq!(settings.palette, ColorPalette::Tritanopia);
+        assert!(settings.patterns);
+        assert!(settings.show_corners);
+        assert!(settings.rotate_view);
+        assert!(settings.focus_tray);
+    }
+
+    #[test]
+    fn broken_values_keep_their_defaults() {
+        let defaults = Settings::default();
+        let settings = Settings::parse(
+            "language =\nfullscreen = maybe\nwindow_size = 1600\nvolume = loud\npalette = Sepia\nno equals sign\nunknown = 1\n",
+        );
+        assert_eq!(settings, defaults);
+    }
+
+    #[test]
+    fn volumes_are_clamped() {
+        let settings = Settings::parse("volume = 3\neffects_volume = 1.5\nui_volume = -1\n");
+        assert_eq!(settings.volume, 1.0);
+        assert_eq!(settings.effects_volume, 1.0);
+        assert_eq!(settings.ui_volume, 0.0);
+    }
+}
@@ -1,8 +1,10 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
 use crate::{
     despawn_screen,
-    game::{player_color, Game, PassTurnEvent},
+    game::{Game, HintEvent, Match, MatchFormat, MoveRecord, PassTurnEvent, PieceControlEvent, TiebreakRule},
+    locale::{Locale, LocalizedText},
     GameState,
 };
 
@@ -13,9 +15,11 @@ pub fn ui_plugin(app: &mut App) {
             Update,
             (
                 button_system,
-                button_action,
-                (print_current_player).run_if(in_state(GameState::Game)),
+                // the game exists only once one is started
+                button_action.run_if(in_state(GameState::Game).or_else(in_state(GameState::GameEnd))),
+                (print_current_player, print_move_history).run_if(in_state(GameState::Game)),
                 (print_winner_info).run_if(in_state(GameState::GameEnd)),
+                print_match_standings.run_if(in_state(GameState::Game).or_else(in_state(GameState::GameEnd))),
             )
             ,
         )
@@ -26,20 +30,36 @@ pub fn ui_plugin(app: &mut App) {
 #[derive(Component)]
 enum UiButtonAction {
     Menu,
+    Rotate,
+    TurnView,
+    Corners,
+    Focus,
+    Hint,
     Pass,
+    Rematch,
+    MainMenu,
 }
 
 #[derive(Component)]
 struct PlayerText;
 
+#[derive(Component)]
+struct MoveHistoryText;
+
+#[derive(Component)]
+struct MatchText;
+
 #[derive(Component)]
 struct OnUiScreen;
 
+// number of the latest moves listed for spectators
+const MOVE_HISTORY_LINES: usize = 20;
+
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
 const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 
-fn setup(mut commands: Commands) {
+fn setup(game: Res<Game>, mut commands: Commands) {
     commands
         .spawn(NodeBundle {
             style: Style {
@@ -58,33 +78,7 @@ fn setup(mut commands: Commands) {
         })
         .insert(OnUiScreen)
         .with_children(|parent| {
-            parent
-                .spawn((
-                    ButtonBundle {
-                        style: Style {
-                            width: Val::Px(150.0),
-                            height: Val::Px(65.0),
-                            border: UiRect::all(Val::Px(5.0)),
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            ..default()
-                        },
-                        border_color: BorderColor(Color::BLACK),
-                        background_color: NORMAL_BUTTON.into(),
-                        ..default()
-                    },
-                    UiButtonAction::Menu,
-                ))
-                .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section(
-                        "Menu",
-                        TextStyle {
-                            font_size: 30.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
-                            ..Default::default()
-                        },
-                    ));
-                });
+            spawn_button(parent, UiButtonAction::Menu, "game.menu");
 
             // game information
             parent.spawn((
@@ -103,42 +97,273 @@ fn setup(mut commands: Commands) {
                 OnUiScreen,
             ));
 
+            // filled after the first game of a match
+            parent.spawn((
+                TextBundle::default(),
+                MatchText,
+                OnUiScreen,
+            ));
+
             parent
-                .spawn((
-                    ButtonBundle {
-                        style: Style {
-                            width: Val::Px(150.0),
-                            height: Val::Px(65.0),
-                            border: UiRect::all(Val::Px(5.0)),
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            ..default()
-                        },
-                        border_color: BorderColor(Color::BLACK),
-                        background_color: NORMAL_BUTTON.into(),
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        column_gap: Val::Px(10.0),
                         ..default()
                     },
-                    UiButtonAction::Pass,
-                ))
+                    ..default()
+                })
                 .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section(
-                        "Give up",
-                        TextStyle {
-                            font_size: 30.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
-                            ..Default::default()
-                        },
-                    ));
+                    // spectators can not move pieces
+                    if !game.is_spectator() {
+                        spawn_button(parent, UiButtonAction::Rotate, "game.rotate");
+                    }
+                    spawn_button(parent, UiButtonAction::TurnView, "game.turn_view");
+                    spawn_button(parent, UiButtonAction::Corners, "game.corners");
+                    spawn_button(parent, UiButtonAction::Focus, "game.focus");
+                    spawn_button(parent, UiButtonAction::Hint, "game.hint");
+                    if !game.is_spectator() {
+                        spawn_button(parent, UiButtonAction::Pass, "game.give_up");
+                    }
                 });
         });
+
+    if game.is_spectator() {
+        commands.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(110.0),
+                right: Val::Px(20.0),
+                ..default()
+            }),
+            MoveHistoryText,
+            OnUiScreen,
+        ));
+    }
+}
+
+fn spawn_button(parent: &mut ChildBuilder, action: UiButtonAction, label: &'static str) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(150.0),
+                    height: Val::Px(65.0),
+                    border: UiRect::all(Val::Px(5.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: BorderColor(Color::BLACK),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 30.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                        ..Default::default()
+                    },
+                ),
+                LocalizedText(label),
+            ));
+        });
 }
 
-fn setup_for_game_end(to_despawn: Query<(Entity, &UiButtonAction)>, mut commands: Commands){
+fn setup_for_game_end(
+    game: Res<Game>,
+    current_match: Option<Res<Match>>,
+    locale: Res<Locale>,
+    to_despawn: Query<(Entity, &UiButtonAction)>,
+    mut commands: Commands,
+) {
     for (entity, uid_button_action) in &to_despawn {
-        if matches!(uid_button_action, UiButtonAction::Pass){
+        // only the menu button is left after the game
+        if !matches!(uid_button_action, UiButtonAction::Menu){
             commands.entity(entity).despawn_recursive();
         }
     }
+
+    let Some(results) = &game.results else {
+        return;
+    };
+    let text_style = |color| TextStyle {
+        font_size: 30.0,
+        color,
+        ..default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(OnUiScreen)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(10.0),
+                        padding: UiRect::all(Val::Px(30.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    let (title, title_color) = match game.winner_player {
+                        Some(index) => (locale.format("results.wins", &[("name", &game.player_name(index))]), game.player_color(index)),
+                        None => (locale.get("results.draw"), Color::WHITE),
+                    };
+                    parent.spawn(TextBundle::from_section(
+                        title,
+                        TextStyle {
+                            font_size: 50.0,
+                            color: title_color,
+                            ..default()
+                        },
+                    ));
+
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                display: Display::Grid,
+                                grid_template_columns: RepeatedGridTrack::auto(5),
+                                column_gap: Val::Px(30.0),
+                                row_gap: Val::Px(5.0),
+                                margin: UiRect::vertical(Val::Px(10.0)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for header in [
+                                "results.rank",
+                                "results.player",
+                                "results.blocks_left",
+                                "results.largest_piece",
+                                "results.pieces_placed",
+                            ] {
+                                parent.spawn((TextBundle::from_section("", text_style(Color::GRAY)), LocalizedText(header)));
+                            }
+                            for result in &results.players {
+                                let color = game.player_color(result.player);
+                                for value in [
+                                    result.rank.to_string(),
+                                    game.player_name(result.player).to_string(),
+                                    result.remaining_blocks.to_string(),
+                                    result.largest_piece.to_string(),
+                                    result.pieces_placed.to_string(),
+                                ] {
+                                    parent.spawn(TextBundle::from_section(value, text_style(color)));
+                                }
+                            }
+                        });
+
+                    let decided_by = match results.decided_by {
+                        TiebreakRule::FewestBlocks => "results.fewest_blocks",
+                        TiebreakRule::SmallestLargestPiece => "results.smallest_largest_piece",
+                        TiebreakRule::PieceValues => "results.piece_values",
+                        TiebreakRule::Draw => "results.tie",
+                    };
+                    parent.spawn((TextBundle::from_section("", text_style(Color::WHITE)), LocalizedText(decided_by)));
+
+                    if let Some(current_match) = &current_match {
+                        spawn_standings(parent, current_match, &locale);
+                    }
+
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Row,
+                                column_gap: Val::Px(10.0),
+                                margin: UiRect::top(Val::Px(20.0)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            let next_game_label = match current_match.as_ref().map(|current_match| current_match.format) {
+                                Some(MatchFormat::Series { .. } | MatchFormat::League) => "results.next_game",
+                                _ => "results.rematch",
+                            };
+                            let match_finished = current_match.as_ref().is_some_and(|current_match| current_match.is_finished());
+                            // the server does not start new games
+                            if !game.is_network_game() && !match_finished {
+                                spawn_button(parent, UiButtonAction::Rematch, next_game_label);
+                            }
+                            spawn_button(parent, UiButtonAction::MainMenu, "results.main_menu");
+                        });
+                });
+        });
+}
+
+// cumulative results of the match so far, the final ranking once it is over
+fn spawn_standings(parent: &mut ChildBuilder, current_match: &Match, locale: &Locale) {
+    let text_style = |color| TextStyle {
+        font_size: 25.0,
+        color,
+        ..default()
+    };
+    let title = match current_match.game_count() {
+        Some(count) if current_match.is_finished() => locale.plural("standings.final", count, &[]),
+        Some(count) => locale.plural("standings.progress", count, &[("played", &current_match.games.len())]),
+        None => locale.plural("standings.open", current_match.games.len(), &[]),
+    };
+    parent.spawn(
+        TextBundle::from_section(title, text_style(Color::WHITE))
+            .with_style(Style { margin: UiRect::top(Val::Px(20.0)), ..default() }),
+    );
+
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::Grid,
+                grid_template_columns: RepeatedGridTrack::auto(5),
+                column_gap: Val::Px(30.0),
+                row_gap: Val::Px(5.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for header in ["standings.place", "standings.name", "standings.points", "standings.wins", "standings.blocks_left"] {
+                parent.spawn((TextBundle::from_section("", text_style(Color::GRAY)), LocalizedText(header)));
+            }
+            for (place, standing) in current_match.standings().iter().enumerate() {
+                let profile = &current_match.players[standing.participant];
+                for value in [
+                    (place + 1).to_string(),
+                    profile.name.clone(),
+                    standing.points.to_string(),
+                    standing.wins.to_string(),
+                    standing.remaining_blocks.to_string(),
+                ] {
+                    parent.spawn(TextBundle::from_section(value, text_style(profile.display_color(current_match.palette))));
+                }
+            }
+        });
 }
 
 fn button_system(
@@ -165,37 +390,149 @@ fn button_system(
     }
 }
 
+// in-game buttons that only pass the press on to the game systems
+#[derive(SystemParam)]
+struct GameButtonEvents<'w> {
+    pass: EventWriter<'w, PassTurnEvent>,
+    hint: EventWriter<'w, HintEvent>,
+    control: EventWriter<'w, PieceControlEvent>,
+}
+
 fn button_action(
     interaction_query: Query<(&Interaction, &UiButtonAction), (Changed<Interaction>, With<Button>)>,
     mut game_state: ResMut<NextState<GameState>>,
-    mut ev_pass: EventWriter<PassTurnEvent>,
+    mut game: ResMut<Game>,
+    current_match: Option<Res<Match>>,
+    mut commands: Commands,
+    mut events: GameButtonEvents,
 ) {
     for (interaction, ui_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match ui_button_action {
-                UiButtonAction::Menu => game_state.set(GameState::Menu),
+                UiButtonAction::Menu | UiButtonAction::MainMenu => game_state.set(GameState::Menu),
+                UiButtonAction::Rematch => {
+                    let next_game = match &current_match {
+                        Some(current_match) => current_match.next_game(&game),
+                        None => Some(game.rematch()),
+                    };
+                    if let Some(next_game) = next_game {
+                        commands.insert_resource(next_game);
+                        game_state.set(GameState::Game);
+                    }
+                }
+                UiButtonAction::Rotate => {
+                    events.control.send(PieceControlEvent::RotateCw);
+                }
+                UiButtonAction::TurnView => game.rotate_view = !game.rotate_view,
+                UiButtonAction::Corners => game.show_corners = !game.show_corners,
+                UiButtonAction::Focus => game.focus_tray = !game.focus_tray,
+                UiButtonAction::Hint => {
+                    events.hint.send(HintEvent);
+                }
                 UiButtonAction::Pass => {
-                    ev_pass.send(PassTurnEvent);
+                    events.pass.send(PassTurnEvent);
                 }
             }
         }
     }
 }
 
-fn print_current_player(game: Res<Game>, mut query: Query<&mut Text, With<PlayerText>>) {
+fn print_current_player(game: Res<Game>, locale: Res<Locale>, mut query: Query<&mut Text, With<PlayerText>>) {
+    let name = game.player_name(game.current_player);
+    let status = if game.no_moves_hinted() {
+        Some("game.no_moves")
+    } else if game.is_absent(game.current_player) {
+        Some("game.disconnected")
+    } else if game.is_spectator() {
+        Some("game.spectating")
+    } else if game.seat() == Some(game.current_player) {
+        Some("game.your_turn")
+    } else {
+        None
+    };
+    for mut text in &mut query {
+        text.sections[0].style.color = game.player_color(game.current_player);
+        text.sections[0].value = match status {
+            Some(key) => locale.format(key, &[("name", &name)]),
+            None => name.to_string(),
+        };
+    }
+}
+
+fn print_move_history(game: Res<Game>, locale: Res<Locale>, mut query: Query<&mut Text, With<MoveHistoryText>>) {
+    if !game.is_changed() {
+        return;
+    }
+
+    let history = game.move_history();
+    let first_shown = history.len().saturating_sub(MOVE_HISTORY_LINES);
     for mut text in &mut query {
-        text.sections[0].style.color = player_color(game.current_player);
-        text.sections[0].value = format!("Player: {}", game.current_player + 1);
+        text.sections = history[first_shown..]
+            .iter()
+            .enumerate()
+            .map(|(index, record)| {
+                let (player, action) = match *record {
+                    MoveRecord::Placed { player, blocks } => (player, locale.plural("history.placed", blocks, &[])),
+                    MoveRecord::Passed { player } => (player, locale.get("history.passed")),
+                };
+                let line = locale.format(
+                    "history.line",
+                    &[("n", &(first_shown + index + 1)), ("name", &game.player_name(player)), ("move", &action)],
+                );
+                TextSection::new(
+                    format!("{line}\n"),
+                    TextStyle {
+                        font_size: 20.0,
+                        color: game.player_color(player),
+                        ..default()
+                    },
+                )
+            })
+            .collect();
+    }
+}
+
+fn print_match_standings(
+    current_match: Option<Res<Match>>,
+    locale: Res<Locale>,
+    mut query: Query<(&mut Text, Ref<MatchText>)>,
+) {
+    let Some(current_match) = current_match.filter(|current_match| !current_match.games.is_empty()) else {
+        return;
+    };
+
+    let section = |value: String, color| TextSection::new(
+        value,
+        TextStyle {
+            font_size: 25.0,
+            color,
+            ..default()
+        },
+    );
+    for (mut text, marker) in &mut query {
+        // the top bar is spawned again for every game
+        if !current_match.is_changed() && !marker.is_added() {
+            continue;
+        }
+        let title = match current_match.game_count() {
+            Some(count) => locale.format("match.progress", &[("played", &current_match.games.len()), ("count", &count)]),
+            None => locale.get("match.open"),
+        };
+        text.sections = vec![section(format!("{title} "), Color::WHITE)];
+        text.sections.extend(current_match.standings().iter().map(|standing| section(
+            format!("{} {}  ", current_match.players[standing.participant].name, standing.points),
+            current_match.players[standing.participant].display_color(current_match.palette),
+        )));
     }
 }
 
-fn print_winner_info(game: Res<Game>, mut query: Query<&mut Text, With<PlayerText>>) {
+fn print_winner_info(game: Res<Game>, locale: Res<Locale>, mut query: Query<&mut Text, With<PlayerText>>) {
     for mut text in &mut query {
         text.sections[0].style.color = Color::WHITE;
         if let Some(index) = game.winner_player {
-            text.sections[0].value = format!("Player: {} is winner", index + 1);
+            text.sections[0].value = locale.format("game.winner", &[("name", &game.player_name(index))]);
         } else {
-            text.sections[0].value = "Draw".to_string();
+            text.sections[0].value = locale.get("game.draw");
         }
 
     }